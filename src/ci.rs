@@ -1,12 +1,17 @@
 mod notifier;
 
-use anyhow::{Context, Result};
-use log::info;
+use crate::config::Config;
+use crate::template::Template;
+
+use anyhow::{Context, Result, anyhow};
+use itertools::Itertools;
+use log::{error, info, warn};
 use notifier::Notifiable;
+pub use notifier::webhook::WebhookConfig;
 use serde::{Deserialize, Serialize};
 use strum_macros::EnumString;
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, EnumString, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum CIKind {
     /// ksnotify is running on GitLab CI.
@@ -17,9 +22,47 @@ pub enum CIKind {
     #[strum(serialize = "github")]
     GitHub,
 
+    /// ksnotify is running on Bitbucket Pipelines for Bitbucket Cloud.
+    #[strum(serialize = "bitbucket")]
+    Bitbucket,
+
+    /// ksnotify is running on Gitea Actions or Forgejo Actions.
+    #[strum(serialize = "gitea")]
+    Gitea,
+
+    /// ksnotify is running on Azure Pipelines.
+    #[strum(serialize = "azure")]
+    Azure,
+
+    /// Post a summary to a Slack channel through an incoming webhook.
+    #[strum(serialize = "slack")]
+    Slack,
+
+    /// Post a JSON payload rendered from a template to any URL.
+    #[strum(serialize = "webhook")]
+    Webhook,
+
     /// ksnotify is running on Local PC (for debug).
     #[strum(serialize = "local")]
     Local,
+
+    /// Print the parse result in JSON without notifying, e.g. for other tools in the pipeline.
+    #[strum(serialize = "json")]
+    Json,
+}
+
+/// Whether a failure of a notifier fails the run when notifying to several notifiers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, EnumString, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailurePolicy {
+    /// Exit with non-zero status if any notifier fails.
+    #[default]
+    #[strum(serialize = "fail")]
+    Fail,
+
+    /// Only log the failures. The other notifiers are run anyway with both policies.
+    #[strum(serialize = "warn")]
+    Warn,
 }
 
 pub struct CI {
@@ -27,7 +70,7 @@ pub struct CI {
 }
 
 impl CI {
-    pub fn new(ci: CIKind) -> Result<Self> {
+    pub fn new(ci: CIKind, config: &Config) -> Result<Self> {
         info!("create ci with {ci:?}");
         match ci {
             CIKind::GitLab => {
@@ -45,7 +88,44 @@ impl CI {
 
                 Ok(Self { notifier })
             }
-            CIKind::Local => unreachable!("Local CI kind should be handled before CI::new"),
+            CIKind::Bitbucket => {
+                let notifier: Box<dyn Notifiable> = Box::new(
+                    notifier::bitbucket::BitbucketNotifier::new()
+                        .with_context(|| "failed to create Bitbucket notifier")?,
+                );
+                Ok(Self { notifier })
+            }
+            CIKind::Gitea => {
+                let notifier: Box<dyn Notifiable> = Box::new(
+                    notifier::gitea::GiteaNotifier::new()
+                        .with_context(|| "failed to create Gitea notifier")?,
+                );
+                Ok(Self { notifier })
+            }
+            CIKind::Azure => {
+                let notifier: Box<dyn Notifiable> = Box::new(
+                    notifier::azure::AzureNotifier::new()
+                        .with_context(|| "failed to create Azure DevOps notifier")?,
+                );
+                Ok(Self { notifier })
+            }
+            CIKind::Slack => {
+                let notifier: Box<dyn Notifiable> = Box::new(
+                    notifier::slack::SlackNotifier::new(config.slack_webhook_url.clone())
+                        .with_context(|| "failed to create Slack notifier")?,
+                );
+                Ok(Self { notifier })
+            }
+            CIKind::Webhook => {
+                let notifier: Box<dyn Notifiable> = Box::new(
+                    notifier::webhook::WebhookNotifier::new(&config.webhook)
+                        .with_context(|| "failed to create webhook notifier")?,
+                );
+                Ok(Self { notifier })
+            }
+            CIKind::Local | CIKind::Json => {
+                unreachable!("Local and JSON CI kinds should be handled before CI::new")
+            }
         }
     }
 
@@ -54,6 +134,81 @@ impl CI {
     }
 }
 
+/// Notifiers of a run. A notifier failing to be created or to notify does not stop the others,
+/// and the failures are summarised by `finish`.
+pub struct Notifiers {
+    notifiers: Vec<(CIKind, CI)>,
+    failures: Vec<(CIKind, anyhow::Error)>,
+    total: usize,
+}
+
+impl Notifiers {
+    /// Create the notifiers of `kinds`. Local and JSON kinds are printed by the caller instead.
+    pub fn new(kinds: &[CIKind], config: &Config) -> Self {
+        let mut notifiers = Vec::new();
+        let mut failures = Vec::new();
+        let mut total = 0;
+        for &kind in kinds {
+            if matches!(kind, CIKind::Local | CIKind::Json) {
+                continue;
+            }
+            total += 1;
+            match CI::new(kind, config) {
+                Ok(ci) => notifiers.push((kind, ci)),
+                Err(err) => failures.push((kind, err)),
+            }
+        }
+        Self {
+            notifiers,
+            failures,
+            total,
+        }
+    }
+
+    /// Job URL of the first notifier knowing it, for the CI link.
+    pub fn job_url(&self) -> Option<String> {
+        self.notifiers
+            .iter()
+            .map(|(_, ci)| ci.job_url())
+            .find(|url| !url.is_empty())
+    }
+
+    pub fn notify(&mut self, template: &Template, patch: bool) {
+        for (kind, ci) in &self.notifiers {
+            info!("notify with {kind:?}");
+            if let Err(err) = ci.notifier.notify(template, patch) {
+                self.failures.push((*kind, err.context("failed to notify")));
+            }
+        }
+    }
+
+    /// Log the failures and return an error if any notifier failed with `FailurePolicy::Fail`.
+    pub fn finish(self, policy: FailurePolicy) -> Result<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        for (kind, err) in &self.failures {
+            error!("{kind:?} notifier failed: {err:#}");
+        }
+        let summary = format!(
+            "{} of {} notifiers failed: {}",
+            self.failures.len(),
+            self.total,
+            self.failures
+                .iter()
+                .map(|(kind, _)| format!("{kind:?}"))
+                .join(", ")
+        );
+        match policy {
+            FailurePolicy::Fail => Err(anyhow!(summary)),
+            FailurePolicy::Warn => {
+                warn!("{summary}");
+                Ok(())
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MergeRequest {
     pub number: Option<u64>,
@@ -62,6 +217,8 @@ pub struct MergeRequest {
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
     use std::str::FromStr;
 
     use super::*;
@@ -72,6 +229,42 @@ mod tests {
         assert_eq!(actual, CIKind::GitHub);
     }
 
+    #[test]
+    fn test_ci_kind_from_str_bitbucket() {
+        let actual = CIKind::from_str("bitbucket").unwrap();
+        assert_eq!(actual, CIKind::Bitbucket);
+    }
+
+    #[test]
+    fn test_ci_kind_from_str_gitea() {
+        let actual = CIKind::from_str("gitea").unwrap();
+        assert_eq!(actual, CIKind::Gitea);
+    }
+
+    #[test]
+    fn test_ci_kind_from_str_azure() {
+        let actual = CIKind::from_str("azure").unwrap();
+        assert_eq!(actual, CIKind::Azure);
+    }
+
+    #[test]
+    fn test_ci_kind_from_str_slack() {
+        let actual = CIKind::from_str("slack").unwrap();
+        assert_eq!(actual, CIKind::Slack);
+    }
+
+    #[test]
+    fn test_ci_kind_from_str_webhook() {
+        let actual = CIKind::from_str("webhook").unwrap();
+        assert_eq!(actual, CIKind::Webhook);
+    }
+
+    #[test]
+    fn test_ci_kind_from_str_json() {
+        let actual = CIKind::from_str("json").unwrap();
+        assert_eq!(actual, CIKind::Json);
+    }
+
     #[test]
     fn test_ci_kind_from_str_gitlab() {
         let actual = CIKind::from_str("gitlab").unwrap();
@@ -89,4 +282,103 @@ mod tests {
         let actual = CIKind::from_str("invalid");
         assert!(actual.is_err());
     }
+
+    #[test]
+    fn test_failure_policy_from_str() {
+        assert_eq!(
+            FailurePolicy::from_str("fail").unwrap(),
+            FailurePolicy::Fail
+        );
+        assert_eq!(
+            FailurePolicy::from_str("warn").unwrap(),
+            FailurePolicy::Warn
+        );
+        assert!(FailurePolicy::from_str("ignore").is_err());
+    }
+
+    struct FakeNotifier {
+        job_url: &'static str,
+        fail: bool,
+        notified: Rc<Cell<usize>>,
+    }
+
+    impl Notifiable for FakeNotifier {
+        fn notify(&self, _template: &Template, _patch: bool) -> Result<()> {
+            self.notified.set(self.notified.get() + 1);
+            if self.fail {
+                anyhow::bail!("unauthorized");
+            }
+            Ok(())
+        }
+
+        fn job_url(&self) -> String {
+            self.job_url.to_string()
+        }
+    }
+
+    fn fake(
+        kind: CIKind,
+        job_url: &'static str,
+        fail: bool,
+        notified: &Rc<Cell<usize>>,
+    ) -> (CIKind, CI) {
+        let notifier = Box::new(FakeNotifier {
+            job_url,
+            fail,
+            notified: Rc::clone(notified),
+        });
+        (kind, CI { notifier })
+    }
+
+    fn template() -> Template {
+        Template::new(Vec::new(), String::new(), None)
+    }
+
+    #[test]
+    fn test_notifiers_isolate_failures() {
+        let notified = Rc::new(Cell::new(0));
+        let mut notifiers = Notifiers {
+            notifiers: vec![
+                fake(CIKind::Slack, "", false, &notified),
+                fake(CIKind::GitHub, "https://github.com/run", true, &notified),
+                fake(CIKind::Webhook, "", false, &notified),
+            ],
+            failures: vec![(CIKind::GitLab, anyhow!("CI_PROJECT_ID must be set"))],
+            total: 4,
+        };
+        assert_eq!(
+            notifiers.job_url().as_deref(),
+            Some("https://github.com/run")
+        );
+        notifiers.notify(&template(), false);
+        // the notifiers after the failed one are run anyway
+        assert_eq!(notified.get(), 3);
+        let err = notifiers.finish(FailurePolicy::Fail).unwrap_err();
+        assert_eq!(err.to_string(), "2 of 4 notifiers failed: GitLab, GitHub");
+    }
+
+    #[test]
+    fn test_notifiers_finish_with_warn_policy() {
+        let notified = Rc::new(Cell::new(0));
+        let mut notifiers = Notifiers {
+            notifiers: vec![fake(CIKind::Slack, "", true, &notified)],
+            failures: Vec::new(),
+            total: 1,
+        };
+        assert_eq!(notifiers.job_url(), None);
+        notifiers.notify(&template(), false);
+        assert!(notifiers.finish(FailurePolicy::Warn).is_ok());
+    }
+
+    #[test]
+    fn test_notifiers_finish_without_failures() {
+        let notified = Rc::new(Cell::new(0));
+        let mut notifiers = Notifiers {
+            notifiers: vec![fake(CIKind::Slack, "", false, &notified)],
+            failures: Vec::new(),
+            total: 1,
+        };
+        notifiers.notify(&template(), false);
+        assert!(notifiers.finish(FailurePolicy::Fail).is_ok());
+    }
 }
@@ -1,10 +1,302 @@
+pub mod azure;
+pub mod bitbucket;
+#[cfg(test)]
+mod fixtures;
+pub mod gitea;
 pub mod github;
 pub mod gitlab;
+#[cfg(test)]
+mod mock_server;
+pub mod slack;
+pub mod webhook;
 use crate::template;
 
 use anyhow::Result;
+use itertools::Itertools;
+use log::debug;
+use std::env;
 
 pub trait Notifiable {
     fn notify(&self, body: &template::Template, patch: bool) -> Result<()>;
     fn job_url(&self) -> String;
 }
+
+/// Link to the job of the CI platform running ksnotify, empty if unknown. For the notifiers not
+/// tied to a CI platform like Slack.
+pub fn job_url_from_env() -> String {
+    if let Ok(url) = env::var("CI_JOB_URL") {
+        // GitLab CI
+        return url;
+    }
+    if let (Ok(server), Ok(repository), Ok(run_id)) = (
+        env::var("GITHUB_SERVER_URL"),
+        env::var("GITHUB_REPOSITORY"),
+        env::var("GITHUB_RUN_ID"),
+    ) {
+        // GitHub Actions and compatible ones
+        return format!("{server}/{repository}/actions/runs/{run_id}");
+    }
+    String::new()
+}
+
+/// What to do with a comment to post a page of the rendered template.
+#[derive(Debug, PartialEq, Eq)]
+enum PageAction {
+    Create(String),
+    Update(u64, String),
+    Delete(u64),
+}
+
+/// Comments on a pull request of a platform, to post each page of the rendered template as a
+/// comment with `post_pages`.
+pub trait PageComments {
+    /// Maximum size of a comment in bytes accepted by the platform.
+    const MAX_COMMENT_SIZE: Option<usize>;
+
+    /// List ids and bodies of the comments in the pull request.
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>>;
+
+    /// Ids of the comments of the same build by page number. By default, the comments whose title
+    /// is the same as the one of the template.
+    fn same_build_comments(
+        &self,
+        template: &template::Template,
+        number: u64,
+    ) -> Result<Vec<(usize, u64)>> {
+        same_build_pages(template, self.list_comments(number)?)
+    }
+
+    /// Whether to post nothing given the comments of the same build. Never by default.
+    fn skip_posting(&self, _template: &template::Template, _same_build: &[(usize, u64)]) -> bool {
+        false
+    }
+
+    fn create_comment(&self, number: u64, body: String) -> Result<()>;
+    fn update_comment(&self, number: u64, id: u64, body: String) -> Result<()>;
+    fn delete_comment(&self, number: u64, id: u64) -> Result<()>;
+}
+
+/// Post the pages of `template` as comments on pull request `number`. With `patch`, the comments
+/// of the same build are updated instead of creating new ones.
+pub fn post_pages<P: PageComments>(
+    platform: &P,
+    number: u64,
+    template: &template::Template,
+    patch: bool,
+) -> Result<()> {
+    let pages = template.render_pages(P::MAX_COMMENT_SIZE)?;
+    let same_build = if patch {
+        platform.same_build_comments(template, number)?
+    } else {
+        Vec::new()
+    };
+    if platform.skip_posting(template, &same_build) {
+        debug!("skip posting the comment");
+        return Ok(());
+    }
+    for action in plan_pages(pages, same_build) {
+        match action {
+            PageAction::Create(body) => platform.create_comment(number, body)?,
+            PageAction::Update(id, body) => platform.update_comment(number, id, body)?,
+            PageAction::Delete(id) => platform.delete_comment(number, id)?,
+        }
+    }
+    Ok(())
+}
+
+/// Page numbers and ids of the `existing` comments (id and body) of the same build as `template`.
+fn same_build_pages(
+    template: &template::Template,
+    existing: Vec<(u64, String)>,
+) -> Result<Vec<(usize, u64)>> {
+    let mut same_build = Vec::new();
+    for (id, body) in existing {
+        if template.is_same_build(&body)? {
+            same_build.push((template::Template::page_number(&body), id));
+        }
+    }
+    Ok(same_build)
+}
+
+/// Plan how to post `pages` given the comments of the same build (page number and id) in the order
+/// they are listed. The comments are updated by their part numbers, the missing pages are created
+/// and the pages left over from a longer previous run are deleted.
+fn plan_pages(pages: Vec<String>, same_build: Vec<(usize, u64)>) -> Vec<PageAction> {
+    // the first comment of each part number is the one to update
+    let same_build: Vec<(usize, u64)> = same_build
+        .into_iter()
+        .unique_by(|(page, _)| *page)
+        .sorted()
+        .collect();
+
+    let mut actions: Vec<PageAction> = pages
+        .into_iter()
+        .enumerate()
+        .map(
+            |(i, page)| match same_build.iter().find(|(n, _)| *n == i + 1) {
+                Some((_, id)) => PageAction::Update(*id, page),
+                None => PageAction::Create(page),
+            },
+        )
+        .collect();
+    let count = actions.len();
+    actions.extend(
+        same_build
+            .into_iter()
+            .filter(|(n, _)| *n > count)
+            .map(|(_, id)| PageAction::Delete(id)),
+    );
+    actions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::template;
+    use super::*;
+    use std::cell::RefCell;
+
+    fn pages(n: usize) -> Vec<String> {
+        (1..=n)
+            .map(|i| format!("## Plan result (test) (part {i}/{n})\nbody {i}"))
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_pages_without_same_build_creates_all_pages() {
+        let actual = plan_pages(pages(2), Vec::new());
+        assert_eq!(
+            actual,
+            pages(2)
+                .into_iter()
+                .map(PageAction::Create)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_plan_pages_updates_and_creates_pages() {
+        let existing = vec![
+            (10, "## Plan result (other)".to_string()),
+            (11, "## Plan result (test)\nold".to_string()),
+        ];
+        let same_build = same_build_pages(&template(), existing).unwrap();
+        let actual = plan_pages(pages(2), same_build);
+        let expected = vec![
+            PageAction::Update(11, pages(2)[0].clone()),
+            PageAction::Create(pages(2)[1].clone()),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_plan_pages_deletes_left_over_pages() {
+        let existing = vec![
+            (21, "## Plan result (test) (part 2/3)".to_string()),
+            (20, "## Plan result (test) (part 1/3)".to_string()),
+            (22, "## Plan result (test) (part 3/3)".to_string()),
+        ];
+        let same_build = same_build_pages(&template(), existing).unwrap();
+        let actual = plan_pages(vec!["single".to_string()], same_build);
+        let expected = vec![
+            PageAction::Update(20, "single".to_string()),
+            PageAction::Delete(21),
+            PageAction::Delete(22),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    /// Platform recording the calls with comments "1: <title>" and "2: <title> (part 2/2)".
+    #[derive(Default)]
+    struct FakeComments {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl PageComments for FakeComments {
+        const MAX_COMMENT_SIZE: Option<usize> = Some(100);
+
+        fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+            self.calls.borrow_mut().push(format!("list {number}"));
+            Ok(vec![
+                (1, "## Plan result (test)".to_string()),
+                (2, "## Plan result (test) (part 2/2)".to_string()),
+            ])
+        }
+
+        fn create_comment(&self, number: u64, body: String) -> Result<()> {
+            assert!(body.len() <= 100);
+            self.calls.borrow_mut().push(format!("create {number}"));
+            Ok(())
+        }
+
+        fn update_comment(&self, number: u64, id: u64, body: String) -> Result<()> {
+            assert!(body.len() <= 100);
+            self.calls
+                .borrow_mut()
+                .push(format!("update {number} {id}"));
+            Ok(())
+        }
+
+        fn delete_comment(&self, number: u64, id: u64) -> Result<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("delete {number} {id}"));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_post_pages_with_patch() {
+        let platform = FakeComments::default();
+        post_pages(&platform, 7, &template(), true).unwrap();
+        assert_eq!(
+            platform.calls.into_inner(),
+            vec!["list 7", "update 7 1", "delete 7 2"]
+        );
+    }
+
+    #[test]
+    fn test_post_pages_without_patch_does_not_list_comments() {
+        let platform = FakeComments::default();
+        post_pages(&platform, 7, &template(), false).unwrap();
+        assert_eq!(platform.calls.into_inner(), vec!["create 7"]);
+    }
+
+    #[test]
+    fn test_job_url_from_env() {
+        temp_env::with_vars(
+            [
+                ("CI_JOB_URL", None),
+                ("GITHUB_SERVER_URL", Some("https://github.com")),
+                ("GITHUB_REPOSITORY", Some("owner/repo")),
+                ("GITHUB_RUN_ID", Some("1")),
+            ],
+            || {
+                assert_eq!(
+                    job_url_from_env(),
+                    "https://github.com/owner/repo/actions/runs/1"
+                );
+            },
+        );
+        temp_env::with_var(
+            "CI_JOB_URL",
+            Some("https://gitlab.com/group/project/-/jobs/1"),
+            || {
+                assert_eq!(
+                    job_url_from_env(),
+                    "https://gitlab.com/group/project/-/jobs/1"
+                );
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("CI_JOB_URL", None::<&str>),
+                ("GITHUB_SERVER_URL", None),
+                ("GITHUB_REPOSITORY", None),
+                ("GITHUB_RUN_ID", None),
+            ],
+            || {
+                assert_eq!(job_url_from_env(), "");
+            },
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use crate::ci::MergeRequest;
+use crate::template::Template;
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::env;
+
+use super::{Notifiable, PageComments, post_pages};
+
+const ENV_AZURE_TOKEN: &str = "KSNOTIFY_AZURE_TOKEN";
+const API_VERSION: &str = "7.1";
+/// Comments are numbered in each thread, so the first one is the page posted by ksnotify.
+const FIRST_COMMENT_ID: u64 = 1;
+/// Property of the threads posted by ksnotify to find them even without `--target`.
+const MARKER_PROPERTY: &str = "ksnotify";
+
+/// Notifier for pull requests of Azure Repos, running on Azure Pipelines. Each page is posted as
+/// a PR thread, which is closed when there is no change and reopened when changes come back.
+/// Nothing is posted if there is no change and no thread to close.
+#[derive(Debug)]
+pub struct AzureNotifier {
+    client: Client,
+    collection_uri: String,
+    project: String,
+    repository: String,
+    token: String,
+    pull_request: MergeRequest,
+    job_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThreadList {
+    value: Vec<Thread>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Thread {
+    id: u64,
+    #[serde(default)]
+    is_deleted: bool,
+    #[serde(default)]
+    comments: Vec<ThreadComment>,
+    #[serde(default)]
+    properties: Option<HashMap<String, Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ThreadComment {
+    id: u64,
+    #[serde(default)]
+    content: String,
+    #[serde(default)]
+    is_deleted: bool,
+}
+
+impl AzureNotifier {
+    pub fn new() -> Result<Self> {
+        info!("create Azure DevOps client");
+
+        let collection_uri = Self::var("SYSTEM_COLLECTIONURI")?;
+        let project = Self::var("SYSTEM_TEAMPROJECT")?;
+        let repository = Self::var("BUILD_REPOSITORY_ID")?;
+        let token = Self::get_token()?;
+        let pull_request = Self::get_pull_request()?;
+        let job_url = Self::get_job_url(&collection_uri, &project)?;
+        debug!(
+            "collection: {collection_uri}, project: {project}, repository: {repository}, pull_request: {pull_request:?}"
+        );
+        Ok(Self {
+            client: Client::new(),
+            collection_uri,
+            project,
+            repository,
+            token,
+            pull_request,
+            job_url,
+        })
+    }
+
+    // Predefined variables in Azure Pipelines
+    // see: https://learn.microsoft.com/en-us/azure/devops/pipelines/build/variables
+    fn get_pull_request() -> Result<MergeRequest> {
+        let number = env::var("SYSTEM_PULLREQUEST_PULLREQUESTID")
+            .ok()
+            .map(|n| n.parse::<u64>())
+            .transpose()?;
+        let commit_sha = Self::var("BUILD_SOURCEVERSION")?;
+        Ok(MergeRequest { number, commit_sha })
+    }
+
+    fn get_job_url(collection_uri: &str, project: &str) -> Result<String> {
+        let build_id = Self::var("BUILD_BUILDID")?;
+        Ok(format!(
+            "{}/{project}/_build/results?buildId={build_id}",
+            collection_uri.trim_end_matches('/')
+        ))
+    }
+
+    /// A personal access token, or `$(System.AccessToken)` mapped to `SYSTEM_ACCESSTOKEN`.
+    fn get_token() -> Result<String> {
+        env::var(ENV_AZURE_TOKEN)
+            .or_else(|_| env::var("SYSTEM_ACCESSTOKEN"))
+            .with_context(|| format!("{ENV_AZURE_TOKEN} or SYSTEM_ACCESSTOKEN must be set"))
+    }
+
+    fn var(key: &str) -> Result<String> {
+        env::var(key).with_context(|| format!("{key} must be set"))
+    }
+
+    /// URL of the threads API of the PR, followed by `path` and the API version.
+    fn threads_url(&self, pr_number: u64, path: &str) -> String {
+        format!(
+            "{}/{}/_apis/git/repositories/{}/pullRequests/{pr_number}/threads{path}?api-version={API_VERSION}",
+            self.collection_uri.trim_end_matches('/'),
+            self.project,
+            self.repository
+        )
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<reqwest::blocking::Response> {
+        let res = request.basic_auth("", Some(&self.token)).send()?;
+        debug!("thread response: {res:?}");
+        Ok(res.error_for_status()?)
+    }
+}
+
+/// Threads of a PR posted with `status`, which is the same for all the pages.
+struct Threads<'a> {
+    notifier: &'a AzureNotifier,
+    status: &'static str,
+}
+
+impl PageComments for Threads<'_> {
+    /// Azure DevOps rejects comments over 150,000 characters.
+    const MAX_COMMENT_SIZE: Option<usize> = Some(150_000);
+
+    /// Ids and bodies of the threads posted by ksnotify, where the body is the one of the first
+    /// comment.
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+        info!("retrieve same build threads");
+        let n = self.notifier;
+        let threads: ThreadList = n.send(n.client.get(n.threads_url(number, "")))?.json()?;
+        Ok(threads
+            .value
+            .into_iter()
+            .filter(|t| !t.is_deleted)
+            .filter(|t| {
+                t.properties
+                    .as_ref()
+                    .is_some_and(|p| p.contains_key(MARKER_PROPERTY))
+            })
+            .filter_map(|t| {
+                let comment = t
+                    .comments
+                    .into_iter()
+                    .find(|c| c.id == FIRST_COMMENT_ID && !c.is_deleted)?;
+                Some((t.id, comment.content))
+            })
+            .collect())
+    }
+
+    /// The threads of ksnotify with the same title, so that they are found without `--target`.
+    fn same_build_comments(&self, template: &Template, number: u64) -> Result<Vec<(usize, u64)>> {
+        let mut same_build = Vec::new();
+        for (id, body) in self.list_comments(number)? {
+            if template.is_same_title(&body)? {
+                same_build.push((Template::page_number(&body), id));
+            }
+        }
+        Ok(same_build)
+    }
+
+    /// A thread is not opened only to be closed.
+    fn skip_posting(&self, template: &Template, same_build: &[(usize, u64)]) -> bool {
+        template.is_no_changes() && same_build.is_empty()
+    }
+
+    fn create_comment(&self, number: u64, body: String) -> Result<()> {
+        let n = self.notifier;
+        let thread = json!({
+            "comments": [{"parentCommentId": 0, "content": body, "commentType": "text"}],
+            "status": self.status,
+            "properties": {MARKER_PROPERTY: {"$type": "System.String", "$value": "true"}},
+        });
+        n.send(n.client.post(n.threads_url(number, "")).json(&thread))?;
+        Ok(())
+    }
+
+    fn update_comment(&self, number: u64, id: u64, body: String) -> Result<()> {
+        let n = self.notifier;
+        n.send(
+            n.client
+                .patch(n.threads_url(number, &format!("/{id}/comments/{FIRST_COMMENT_ID}")))
+                .json(&json!({ "content": body })),
+        )?;
+        self.set_status(number, id, self.status)
+    }
+
+    /// Threads may have replies, so the pages left over are closed instead of deleted.
+    fn delete_comment(&self, number: u64, id: u64) -> Result<()> {
+        self.set_status(number, id, "closed")
+    }
+}
+
+impl Threads<'_> {
+    fn set_status(&self, number: u64, id: u64, status: &str) -> Result<()> {
+        let n = self.notifier;
+        n.send(
+            n.client
+                .patch(n.threads_url(number, &format!("/{id}")))
+                .json(&json!({ "status": status })),
+        )?;
+        Ok(())
+    }
+}
+
+impl Notifiable for AzureNotifier {
+    fn notify(&self, template: &Template, patch: bool) -> Result<()> {
+        info!("notify to Azure DevOps");
+
+        let pr_number = if let Some(n) = self.pull_request.number {
+            n
+        } else {
+            debug!("pull request number is None");
+            return Ok(());
+        };
+
+        // nothing to review if the diff becomes empty
+        let status = if template.is_no_changes() {
+            "closed"
+        } else {
+            "active"
+        };
+        let threads = Threads {
+            notifier: self,
+            status,
+        };
+        if !patch && template.is_no_changes() {
+            // nothing to post, but the threads of previous runs are closed even without `--patch`
+            for (_, id) in threads.same_build_comments(template, pr_number)? {
+                threads.set_status(pr_number, id, "closed")?;
+            }
+            return Ok(());
+        }
+        post_pages(&threads, pr_number, template, patch)
+    }
+
+    fn job_url(&self) -> String {
+        self.job_url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{template, template_of};
+    use super::super::mock_server::MockServer;
+    use super::*;
+
+    const THREADS_PATH: &str = "/org/project/_apis/git/repositories/repo-id/pullRequests/3/threads";
+
+    fn notifier(server_url: &str, number: Option<u64>) -> AzureNotifier {
+        AzureNotifier {
+            client: Client::new(),
+            collection_uri: format!("{server_url}/org/"),
+            project: "project".to_string(),
+            repository: "repo-id".to_string(),
+            token: "token".to_string(),
+            pull_request: MergeRequest {
+                number,
+                commit_sha: "abc123".to_string(),
+            },
+            job_url: String::new(),
+        }
+    }
+
+    fn threads() -> String {
+        let marker = json!({"ksnotify": {"$type": "System.String", "$value": "true"}});
+        json!({"value": [
+            {"id": 10, "properties": marker, "comments": [{"id": 1, "content": "## Plan result (other)"}]},
+            {"id": 11, "properties": marker, "isDeleted": true, "comments": [{"id": 1, "content": "## Plan result (test)"}]},
+            // posted by someone else
+            {"id": 13, "comments": [{"id": 1, "content": "## Plan result (test)"}]},
+            {"id": 12, "properties": marker, "comments": [{"id": 1, "content": "## Plan result (test)\nold"}]},
+            {"id": 14, "properties": marker, "comments": [{"id": 1, "content": "## Plan result (test) (part 2/2)"}]},
+        ]})
+        .to_string()
+    }
+
+    #[test]
+    fn test_get_job_url() {
+        temp_env::with_var("BUILD_BUILDID", Some("99"), || {
+            let job_url =
+                AzureNotifier::get_job_url("https://dev.azure.com/org/", "project").unwrap();
+            assert_eq!(
+                job_url,
+                "https://dev.azure.com/org/project/_build/results?buildId=99"
+            );
+        });
+    }
+
+    #[test]
+    fn test_get_pull_request() {
+        temp_env::with_vars(
+            [
+                ("SYSTEM_PULLREQUEST_PULLREQUESTID", Some("3")),
+                ("BUILD_SOURCEVERSION", Some("abc123")),
+            ],
+            || {
+                let pull_request = AzureNotifier::get_pull_request().unwrap();
+                assert_eq!(pull_request.number, Some(3));
+                assert_eq!(pull_request.commit_sha, "abc123");
+            },
+        );
+    }
+
+    #[test]
+    fn test_get_token_falls_back_to_system_access_token() {
+        temp_env::with_vars(
+            [
+                (ENV_AZURE_TOKEN, None),
+                ("SYSTEM_ACCESSTOKEN", Some("system-token")),
+            ],
+            || {
+                assert_eq!(AzureNotifier::get_token().unwrap(), "system-token");
+            },
+        );
+        temp_env::with_vars(
+            [
+                (ENV_AZURE_TOKEN, None::<&str>),
+                ("SYSTEM_ACCESSTOKEN", None),
+            ],
+            || {
+                assert!(AzureNotifier::get_token().is_err());
+            },
+        );
+    }
+
+    #[test]
+    fn test_notify_creates_active_thread() {
+        let server = MockServer::start(vec![(200, "{}")]);
+        notifier(server.url(), Some(3))
+            .notify(&template(), false)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, format!("{THREADS_PATH}?api-version=7.1"));
+        // basic auth with an empty user name and the token
+        assert_eq!(requests[0].header("authorization"), Some("Basic OnRva2Vu"));
+        let body = requests[0].json();
+        assert_eq!(body["status"], "active");
+        assert_eq!(body["properties"]["ksnotify"]["$value"], "true");
+        assert_eq!(body["comments"][0]["content"], template().render().unwrap());
+    }
+
+    #[test]
+    fn test_notify_with_patch_updates_and_reopens_thread() {
+        let threads = threads();
+        let server =
+            MockServer::start(vec![(200, &threads), (200, "{}"), (200, "{}"), (200, "{}")]);
+        notifier(server.url(), Some(3))
+            .notify(&template(), true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 4);
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(
+            requests[1].path,
+            format!("{THREADS_PATH}/12/comments/1?api-version=7.1")
+        );
+        assert_eq!(requests[2].method, "PATCH");
+        assert_eq!(
+            requests[2].path,
+            format!("{THREADS_PATH}/12?api-version=7.1")
+        );
+        assert_eq!(requests[2].json(), json!({"status": "active"}));
+        // the page left over is closed
+        assert_eq!(
+            requests[3].path,
+            format!("{THREADS_PATH}/14?api-version=7.1")
+        );
+        assert_eq!(requests[3].json(), json!({"status": "closed"}));
+    }
+
+    #[test]
+    fn test_notify_with_patch_finds_thread_without_target() {
+        let threads = json!({"value": [
+            {"id": 12, "properties": {"ksnotify": {"$type": "System.String", "$value": "true"}},
+             "comments": [{"id": 1, "content": "## Plan result\nold"}]},
+        ]})
+        .to_string();
+        let server = MockServer::start(vec![(200, &threads), (200, "{}"), (200, "{}")]);
+        let template = Template::new(Vec::new(), String::new(), None);
+        notifier(server.url(), Some(3))
+            .notify(&template, true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            requests[1].path,
+            format!("{THREADS_PATH}/12/comments/1?api-version=7.1")
+        );
+        assert_eq!(requests[2].json(), json!({"status": "closed"}));
+    }
+
+    #[test]
+    fn test_notify_without_changes_and_thread_posts_nothing() {
+        let server = MockServer::start(vec![(200, r#"{"value": []}"#)]);
+        notifier(server.url(), Some(3))
+            .notify(&template_of(Vec::new()), true)
+            .unwrap();
+        assert_eq!(server.requests().len(), 1);
+
+        let server = MockServer::start(vec![(200, r#"{"value": []}"#)]);
+        notifier(server.url(), Some(3))
+            .notify(&template_of(Vec::new()), false)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "GET");
+    }
+
+    #[test]
+    fn test_notify_without_patch_closes_threads_without_changes() {
+        let threads = threads();
+        let server = MockServer::start(vec![(200, &threads), (200, "{}"), (200, "{}")]);
+        notifier(server.url(), Some(3))
+            .notify(&template_of(Vec::new()), false)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        for (request, id) in requests[1..].iter().zip([12, 14]) {
+            assert_eq!(request.method, "PATCH");
+            assert_eq!(request.path, format!("{THREADS_PATH}/{id}?api-version=7.1"));
+            assert_eq!(request.json(), json!({"status": "closed"}));
+        }
+    }
+
+    #[test]
+    fn test_notify_with_patch_closes_thread_without_changes() {
+        let threads = threads();
+        let server =
+            MockServer::start(vec![(200, &threads), (200, "{}"), (200, "{}"), (200, "{}")]);
+        notifier(server.url(), Some(3))
+            .notify(&template_of(Vec::new()), true)
+            .unwrap();
+        let requests = server.requests();
+        assert!(
+            requests[1].json()["content"]
+                .as_str()
+                .unwrap()
+                .contains("No changes.")
+        );
+        assert_eq!(requests[2].json(), json!({"status": "closed"}));
+    }
+
+    #[test]
+    fn test_notify_without_pull_request_does_nothing() {
+        let server = MockServer::start(vec![]);
+        notifier(server.url(), None)
+            .notify(&template(), true)
+            .unwrap();
+        assert!(server.requests().is_empty());
+    }
+}
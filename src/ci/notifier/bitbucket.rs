@@ -0,0 +1,409 @@
+use crate::ci::MergeRequest;
+use crate::template::Template;
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+
+use super::{Notifiable, PageComments, post_pages};
+
+const ENV_BITBUCKET_TOKEN: &str = "KSNOTIFY_BITBUCKET_TOKEN";
+const ENV_BITBUCKET_API_URL: &str = "KSNOTIFY_BITBUCKET_API_URL";
+const DEFAULT_API_URL: &str = "https://api.bitbucket.org/2.0";
+const LIST_COMMENTS_PAGE_LENGTH: usize = 100;
+
+/// Notifier for pull requests of Bitbucket Cloud, running on Bitbucket Pipelines. Bitbucket Server
+/// (Data Center) is not supported as its REST API is different.
+#[derive(Debug)]
+pub struct BitbucketNotifier {
+    client: Client,
+    api_url: String,
+    token: String,
+    repository: String,
+    pull_request: MergeRequest,
+    job_url: String,
+}
+
+/// A page of `GET /pullrequests/{id}/comments`.
+#[derive(Debug, Deserialize)]
+struct CommentPage {
+    values: Vec<Comment>,
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comment {
+    id: u64,
+    content: Content,
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct Content {
+    raw: String,
+}
+
+impl BitbucketNotifier {
+    pub fn new() -> Result<Self> {
+        info!("create Bitbucket client");
+
+        let token = Self::get_token()?;
+        let repository = Self::get_repository()?;
+        let pull_request = Self::get_pull_request()?;
+        let job_url = Self::get_job_url()?;
+        let api_url = Self::get_api_url()?;
+        debug!("repository: {repository}, pull_request: {pull_request:?}");
+        Ok(Self {
+            client: Client::new(),
+            api_url,
+            token,
+            repository,
+            pull_request,
+            job_url,
+        })
+    }
+
+    fn get_api_url() -> Result<String> {
+        let api_url = env::var(ENV_BITBUCKET_API_URL).unwrap_or_else(|_| DEFAULT_API_URL.into());
+        // REST API of Bitbucket Server and Data Center like https://bitbucket.example.com/rest/api/1.0
+        if api_url.contains("/rest/api/") {
+            anyhow::bail!(
+                "{ENV_BITBUCKET_API_URL} must be a Bitbucket Cloud API URL, Bitbucket Server is not supported: {api_url}"
+            );
+        }
+        Ok(api_url)
+    }
+
+    // Default variables in Bitbucket Pipelines
+    // see: https://support.atlassian.com/bitbucket-cloud/docs/variables-and-secrets/
+    fn get_pull_request() -> Result<MergeRequest> {
+        let number = env::var("BITBUCKET_PR_ID")
+            .ok()
+            .map(|n| n.parse::<u64>())
+            .transpose()?;
+        let commit_sha = env::var("BITBUCKET_COMMIT").context("BITBUCKET_COMMIT must be set")?;
+        Ok(MergeRequest { number, commit_sha })
+    }
+
+    fn get_job_url() -> Result<String> {
+        let repository = Self::get_repository()?;
+        let build_number =
+            env::var("BITBUCKET_BUILD_NUMBER").context("BITBUCKET_BUILD_NUMBER must be set")?;
+        Ok(format!(
+            "https://bitbucket.org/{repository}/pipelines/results/{build_number}"
+        ))
+    }
+
+    fn get_token() -> Result<String> {
+        env::var(ENV_BITBUCKET_TOKEN).with_context(|| format!("{ENV_BITBUCKET_TOKEN} must be set"))
+    }
+
+    fn get_repository() -> Result<String> {
+        // BITBUCKET_REPO_FULL_NAME is like <workspace>/<repo>
+        let repository =
+            env::var("BITBUCKET_REPO_FULL_NAME").context("BITBUCKET_REPO_FULL_NAME must be set")?;
+        if !repository.contains('/') {
+            anyhow::bail!("BITBUCKET_REPO_FULL_NAME must be in <workspace>/<repo> format");
+        }
+        Ok(repository)
+    }
+
+    fn comments_url(&self, pr_number: u64) -> String {
+        format!(
+            "{}/repositories/{}/pullrequests/{pr_number}/comments",
+            self.api_url.trim_end_matches('/'),
+            self.repository
+        )
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<()> {
+        let res = request.bearer_auth(&self.token).send()?;
+        debug!("comment response: {res:?}");
+        res.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl PageComments for BitbucketNotifier {
+    /// Bitbucket Cloud rejects large comments, so the pages are kept below 32 KiB.
+    const MAX_COMMENT_SIZE: Option<usize> = Some(32_768);
+
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+        info!("retrieve same build comments");
+        let mut comments = Vec::new();
+        let mut url = Some(format!(
+            "{}?pagelen={LIST_COMMENTS_PAGE_LENGTH}",
+            self.comments_url(number)
+        ));
+        while let Some(next) = url {
+            let page: CommentPage = self
+                .client
+                .get(&next)
+                .bearer_auth(&self.token)
+                .send()?
+                .error_for_status()?
+                .json()?;
+            comments.extend(
+                page.values
+                    .into_iter()
+                    .filter(|c| !c.deleted)
+                    .map(|c| (c.id, c.content.raw)),
+            );
+            url = page.next;
+        }
+        Ok(comments)
+    }
+
+    fn create_comment(&self, number: u64, body: String) -> Result<()> {
+        self.send(
+            self.client
+                .post(self.comments_url(number))
+                .json(&json!({"content": {"raw": body}})),
+        )
+    }
+
+    fn update_comment(&self, number: u64, id: u64, body: String) -> Result<()> {
+        self.send(
+            self.client
+                .put(format!("{}/{id}", self.comments_url(number)))
+                .json(&json!({"content": {"raw": body}})),
+        )
+    }
+
+    fn delete_comment(&self, number: u64, id: u64) -> Result<()> {
+        self.send(
+            self.client
+                .delete(format!("{}/{id}", self.comments_url(number))),
+        )
+    }
+}
+
+impl Notifiable for BitbucketNotifier {
+    fn notify(&self, template: &Template, patch: bool) -> Result<()> {
+        info!("notify to Bitbucket");
+
+        let pr_number = if let Some(n) = self.pull_request.number {
+            n
+        } else {
+            debug!("pull request number is None");
+            return Ok(());
+        };
+
+        post_pages(self, pr_number, template, patch)
+    }
+
+    fn job_url(&self) -> String {
+        self.job_url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::{template, template_of};
+    use super::super::mock_server::MockServer;
+    use super::*;
+    use crate::resource::ResourceChange;
+
+    fn notifier(api_url: &str, number: Option<u64>) -> BitbucketNotifier {
+        BitbucketNotifier {
+            client: Client::new(),
+            api_url: api_url.to_string(),
+            token: "token".to_string(),
+            repository: "workspace/repo".to_string(),
+            pull_request: MergeRequest {
+                number,
+                commit_sha: "abc123".to_string(),
+            },
+            job_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_repository() {
+        temp_env::with_var("BITBUCKET_REPO_FULL_NAME", Some("workspace/repo"), || {
+            assert_eq!(
+                BitbucketNotifier::get_repository().unwrap(),
+                "workspace/repo"
+            );
+        });
+        temp_env::with_var("BITBUCKET_REPO_FULL_NAME", Some("repo"), || {
+            assert!(BitbucketNotifier::get_repository().is_err());
+        });
+    }
+
+    #[test]
+    fn test_get_api_url() {
+        temp_env::with_var(ENV_BITBUCKET_API_URL, None::<&str>, || {
+            assert_eq!(BitbucketNotifier::get_api_url().unwrap(), DEFAULT_API_URL);
+        });
+        temp_env::with_var(
+            ENV_BITBUCKET_API_URL,
+            Some("https://bitbucket.example.com/rest/api/1.0"),
+            || {
+                let err = BitbucketNotifier::get_api_url().unwrap_err();
+                assert!(
+                    err.to_string()
+                        .contains("Bitbucket Server is not supported")
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_get_job_url() {
+        temp_env::with_vars(
+            [
+                ("BITBUCKET_REPO_FULL_NAME", Some("workspace/repo")),
+                ("BITBUCKET_BUILD_NUMBER", Some("42")),
+            ],
+            || {
+                let job_url = BitbucketNotifier::get_job_url().unwrap();
+                assert_eq!(
+                    job_url,
+                    "https://bitbucket.org/workspace/repo/pipelines/results/42"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_get_pull_request() {
+        temp_env::with_vars(
+            [
+                ("BITBUCKET_PR_ID", Some("7")),
+                ("BITBUCKET_COMMIT", Some("abc123")),
+            ],
+            || {
+                let pull_request = BitbucketNotifier::get_pull_request().unwrap();
+                assert_eq!(pull_request.number, Some(7));
+                assert_eq!(pull_request.commit_sha, "abc123");
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("BITBUCKET_PR_ID", None),
+                ("BITBUCKET_COMMIT", Some("abc123")),
+            ],
+            || {
+                let pull_request = BitbucketNotifier::get_pull_request().unwrap();
+                assert_eq!(pull_request.number, None);
+            },
+        );
+    }
+
+    #[test]
+    fn test_notify_creates_comment() {
+        let server = MockServer::start(vec![(201, "{}")]);
+        notifier(server.url(), Some(7))
+            .notify(&template(), false)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].path,
+            "/repositories/workspace/repo/pullrequests/7/comments"
+        );
+        assert_eq!(requests[0].header("authorization"), Some("Bearer token"));
+        assert_eq!(
+            requests[0].json()["content"]["raw"],
+            template().render().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_notify_splits_long_plan_into_comments() {
+        let diff = format!("+{}", "a".repeat(1000));
+        let results = (0..50)
+            .map(|i| ResourceChange::new(&format!("v1.ConfigMap.default.c{i}"), &diff).unwrap())
+            .collect();
+        let server = MockServer::start(vec![(201, "{}"); 3]);
+        notifier(server.url(), Some(7))
+            .notify(&template_of(results), false)
+            .unwrap();
+        let requests = server.requests();
+        assert!(requests.len() > 1);
+        for request in &requests {
+            let body = request.json()["content"]["raw"]
+                .as_str()
+                .unwrap()
+                .to_string();
+            assert!(body.len() <= 32_768);
+        }
+    }
+
+    #[test]
+    fn test_notify_with_patch_updates_same_build_comment() {
+        let comments = json!({"values": [
+            {"id": 1, "content": {"raw": "## Plan result (other)"}},
+            {"id": 2, "content": {"raw": "## Plan result (test)\nold"}},
+        ]})
+        .to_string();
+        let server = MockServer::start(vec![(200, &comments), (200, "{}")]);
+        notifier(server.url(), Some(7))
+            .notify(&template(), true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(
+            requests[0].path,
+            "/repositories/workspace/repo/pullrequests/7/comments?pagelen=100"
+        );
+        assert_eq!(requests[1].method, "PUT");
+        assert_eq!(
+            requests[1].path,
+            "/repositories/workspace/repo/pullrequests/7/comments/2"
+        );
+    }
+
+    #[test]
+    fn test_notify_follows_next_page_of_comments() {
+        let server = MockServer::start_with(|url| {
+            vec![
+                (
+                    200,
+                    format!(
+                        r#"{{"values": [], "next": "{url}/repositories/workspace/repo/pullrequests/7/comments?page=2"}}"#
+                    ),
+                ),
+                (
+                    200,
+                    json!({"values": [{"id": 3, "content": {"raw": "## Plan result (test)"}}]})
+                        .to_string(),
+                ),
+                (200, "{}".to_string()),
+            ]
+        });
+        notifier(server.url(), Some(7))
+            .notify(&template(), true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(
+            requests[1].path,
+            "/repositories/workspace/repo/pullrequests/7/comments?page=2"
+        );
+        assert_eq!(requests[2].method, "PUT");
+        assert!(requests[2].path.ends_with("/comments/3"));
+    }
+
+    #[test]
+    fn test_notify_without_pull_request_does_nothing() {
+        let server = MockServer::start(vec![]);
+        notifier(server.url(), None)
+            .notify(&template(), true)
+            .unwrap();
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_notify_returns_error_on_failure() {
+        let server = MockServer::start(vec![(403, r#"{"error": {}}"#)]);
+        let actual = notifier(server.url(), Some(7)).notify(&template(), false);
+        assert!(actual.is_err());
+    }
+}
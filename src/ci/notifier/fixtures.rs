@@ -0,0 +1,14 @@
+use crate::resource::ResourceChange;
+use crate::template::Template;
+
+/// Template of the target "test" with a configured Service.
+pub fn template() -> Template {
+    template_of(vec![
+        ResourceChange::new("v1.Service.default.svc", "-a\n+b").unwrap(),
+    ])
+}
+
+/// Template of the target "test" with the given changes.
+pub fn template_of(results: Vec<ResourceChange>) -> Template {
+    Template::new(results, String::new(), Some("test".to_string()))
+}
@@ -0,0 +1,368 @@
+use crate::ci::MergeRequest;
+use crate::template::Template;
+
+use anyhow::{Context, Result, anyhow};
+use log::{debug, info};
+use reqwest::blocking::{Client, RequestBuilder};
+use serde::Deserialize;
+use serde_json::json;
+use std::env;
+
+use super::{Notifiable, PageComments, post_pages};
+
+const ENV_GITEA_TOKEN: &str = "KSNOTIFY_GITEA_TOKEN";
+const ENV_GITEA_URL: &str = "KSNOTIFY_GITEA_URL";
+
+/// Notifier for pull requests of Gitea or Forgejo, running on Gitea Actions (or Forgejo Actions).
+#[derive(Debug)]
+pub struct GiteaNotifier {
+    client: Client,
+    server_url: String,
+    token: String,
+    repository: String,
+    pull_request: MergeRequest,
+    job_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Comment {
+    id: u64,
+    body: String,
+}
+
+impl GiteaNotifier {
+    pub fn new() -> Result<Self> {
+        info!("create Gitea client");
+
+        let server_url = Self::get_server_url()?;
+        let token = Self::get_token()?;
+        let repository = Self::get_repository()?;
+        let pull_request = Self::get_pull_request()?;
+        let job_url = Self::get_job_url(&server_url, &repository)?;
+        debug!("server: {server_url}, repository: {repository}, pull_request: {pull_request:?}");
+        Ok(Self {
+            client: Client::new(),
+            server_url,
+            token,
+            repository,
+            pull_request,
+            job_url,
+        })
+    }
+
+    // Gitea Actions sets GITEA_ACTIONS=true and GitHub compatible variables like GITHUB_SERVER_URL.
+    // Forgejo Actions also sets FORGEJO_* ones.
+    // see: https://docs.gitea.com/usage/actions/comparison
+    fn get_server_url() -> Result<String> {
+        if let Ok(url) = env::var(ENV_GITEA_URL) {
+            return Ok(url);
+        }
+        if !Self::is_gitea_actions() {
+            return Err(anyhow!(
+                "{ENV_GITEA_URL} must be set when not running on Gitea Actions"
+            ));
+        }
+        Self::var(&["GITEA_SERVER_URL", "GITHUB_SERVER_URL"])
+    }
+
+    fn is_gitea_actions() -> bool {
+        env::var("GITEA_ACTIONS").is_ok_and(|v| v == "true")
+            || env::var("FORGEJO_ACTIONS").is_ok_and(|v| v == "true")
+    }
+
+    fn get_token() -> Result<String> {
+        Self::var(&[ENV_GITEA_TOKEN, "GITEA_TOKEN", "GITHUB_TOKEN"])
+    }
+
+    fn get_repository() -> Result<String> {
+        // GITHUB_REPOSITORY is like <owner>/<repo>
+        let repository = Self::var(&["GITEA_REPOSITORY", "GITHUB_REPOSITORY"])?;
+        if !repository.contains('/') {
+            return Err(anyhow!("repository must be in <owner>/<repo> format"));
+        }
+        Ok(repository)
+    }
+
+    fn get_pull_request() -> Result<MergeRequest> {
+        // GITHUB_REF is like refs/pull/<number>/head on Gitea Actions
+        let git_ref = Self::var(&["GITEA_REF", "GITHUB_REF"]).unwrap_or_default();
+        let number = git_ref
+            .strip_prefix("refs/pull/")
+            .and_then(|rest| rest.split('/').next())
+            .map(str::parse::<u64>)
+            .transpose()?;
+        let commit_sha = Self::var(&["GITEA_SHA", "GITHUB_SHA"])?;
+        Ok(MergeRequest { number, commit_sha })
+    }
+
+    fn get_job_url(server_url: &str, repository: &str) -> Result<String> {
+        let run_number = Self::var(&["GITEA_RUN_NUMBER", "GITHUB_RUN_NUMBER"])?;
+        Ok(format!(
+            "{}/{repository}/actions/runs/{run_number}",
+            server_url.trim_end_matches('/')
+        ))
+    }
+
+    /// Value of the first environment variable set in `keys`.
+    fn var(keys: &[&str]) -> Result<String> {
+        keys.iter()
+            .find_map(|key| env::var(key).ok())
+            .with_context(|| format!("{} must be set", keys.join(" or ")))
+    }
+
+    fn api_url(&self) -> String {
+        format!(
+            "{}/api/v1/repos/{}",
+            self.server_url.trim_end_matches('/'),
+            self.repository
+        )
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<()> {
+        let res = request
+            .header("Authorization", format!("token {}", self.token))
+            .send()?;
+        debug!("comment response: {res:?}");
+        res.error_for_status()?;
+        Ok(())
+    }
+}
+
+impl PageComments for GiteaNotifier {
+    const MAX_COMMENT_SIZE: Option<usize> = None;
+
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+        info!("retrieve same build comments");
+        // the endpoint does not paginate (only `since` and `before` are supported), so a single
+        // request returns all the comments
+        let url = format!("{}/issues/{number}/comments", self.api_url());
+        let comments: Vec<Comment> = self
+            .client
+            .get(url)
+            .header("Authorization", format!("token {}", self.token))
+            .send()?
+            .error_for_status()?
+            .json()?;
+        Ok(comments.into_iter().map(|c| (c.id, c.body)).collect())
+    }
+
+    fn create_comment(&self, number: u64, body: String) -> Result<()> {
+        self.send(
+            self.client
+                .post(format!("{}/issues/{number}/comments", self.api_url()))
+                .json(&json!({ "body": body })),
+        )
+    }
+
+    fn update_comment(&self, _number: u64, id: u64, body: String) -> Result<()> {
+        self.send(
+            self.client
+                .patch(format!("{}/issues/comments/{id}", self.api_url()))
+                .json(&json!({ "body": body })),
+        )
+    }
+
+    fn delete_comment(&self, _number: u64, id: u64) -> Result<()> {
+        self.send(
+            self.client
+                .delete(format!("{}/issues/comments/{id}", self.api_url())),
+        )
+    }
+}
+
+impl Notifiable for GiteaNotifier {
+    fn notify(&self, template: &Template, patch: bool) -> Result<()> {
+        info!("notify to Gitea");
+
+        let pr_number = if let Some(n) = self.pull_request.number {
+            n
+        } else {
+            debug!("pull request number is None");
+            return Ok(());
+        };
+
+        post_pages(self, pr_number, template, patch)
+    }
+
+    fn job_url(&self) -> String {
+        self.job_url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::template;
+    use super::super::mock_server::MockServer;
+    use super::*;
+
+    fn notifier(server_url: &str, number: Option<u64>) -> GiteaNotifier {
+        GiteaNotifier {
+            client: Client::new(),
+            server_url: server_url.to_string(),
+            token: "token".to_string(),
+            repository: "owner/repo".to_string(),
+            pull_request: MergeRequest {
+                number,
+                commit_sha: "abc123".to_string(),
+            },
+            job_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_server_url_on_gitea_actions() {
+        temp_env::with_vars(
+            [
+                (ENV_GITEA_URL, None),
+                ("GITEA_ACTIONS", Some("true")),
+                ("GITEA_SERVER_URL", None),
+                ("GITHUB_SERVER_URL", Some("https://gitea.example.com")),
+            ],
+            || {
+                let url = GiteaNotifier::get_server_url().unwrap();
+                assert_eq!(url, "https://gitea.example.com");
+            },
+        );
+    }
+
+    #[test]
+    fn test_get_server_url_outside_gitea_actions_needs_explicit_url() {
+        temp_env::with_vars(
+            [
+                (ENV_GITEA_URL, None),
+                ("GITEA_ACTIONS", None),
+                ("FORGEJO_ACTIONS", None),
+                ("GITHUB_SERVER_URL", Some("https://github.com")),
+            ],
+            || {
+                assert!(GiteaNotifier::get_server_url().is_err());
+            },
+        );
+        temp_env::with_var(ENV_GITEA_URL, Some("https://forgejo.example.com"), || {
+            let url = GiteaNotifier::get_server_url().unwrap();
+            assert_eq!(url, "https://forgejo.example.com");
+        });
+    }
+
+    #[test]
+    fn test_get_pull_request() {
+        temp_env::with_vars(
+            [
+                ("GITEA_REF", None),
+                ("GITHUB_REF", Some("refs/pull/12/head")),
+                ("GITEA_SHA", None),
+                ("GITHUB_SHA", Some("abc123")),
+            ],
+            || {
+                let pull_request = GiteaNotifier::get_pull_request().unwrap();
+                assert_eq!(pull_request.number, Some(12));
+                assert_eq!(pull_request.commit_sha, "abc123");
+            },
+        );
+        temp_env::with_vars(
+            [
+                ("GITEA_REF", None),
+                ("GITHUB_REF", Some("refs/heads/main")),
+                ("GITEA_SHA", None),
+                ("GITHUB_SHA", Some("abc123")),
+            ],
+            || {
+                let pull_request = GiteaNotifier::get_pull_request().unwrap();
+                assert_eq!(pull_request.number, None);
+            },
+        );
+    }
+
+    #[test]
+    fn test_get_job_url() {
+        temp_env::with_vars(
+            [("GITEA_RUN_NUMBER", None), ("GITHUB_RUN_NUMBER", Some("5"))],
+            || {
+                let job_url =
+                    GiteaNotifier::get_job_url("https://gitea.example.com/", "owner/repo").unwrap();
+                assert_eq!(
+                    job_url,
+                    "https://gitea.example.com/owner/repo/actions/runs/5"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_notify_creates_comment() {
+        let server = MockServer::start(vec![(201, "{}")]);
+        notifier(server.url(), Some(12))
+            .notify(&template(), false)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(
+            requests[0].path,
+            "/api/v1/repos/owner/repo/issues/12/comments"
+        );
+        assert_eq!(requests[0].header("authorization"), Some("token token"));
+        assert_eq!(requests[0].json()["body"], template().render().unwrap());
+    }
+
+    #[test]
+    fn test_notify_with_patch_updates_same_build_comment() {
+        let comments = json!([
+            {"id": 1, "body": "## Plan result (other)"},
+            {"id": 2, "body": "## Plan result (test)\nold"},
+        ])
+        .to_string();
+        let server = MockServer::start(vec![(200, &comments), (200, "{}")]);
+        notifier(server.url(), Some(12))
+            .notify(&template(), true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].path,
+            "/api/v1/repos/owner/repo/issues/12/comments"
+        );
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(
+            requests[1].path,
+            "/api/v1/repos/owner/repo/issues/comments/2"
+        );
+    }
+
+    #[test]
+    fn test_notify_with_patch_lists_many_comments_in_one_request() {
+        let mut comments: Vec<serde_json::Value> = (1..=60)
+            .map(|id| json!({"id": id, "body": format!("comment {id}")}))
+            .collect();
+        comments.push(json!({"id": 61, "body": "## Plan result (test)\nold"}));
+        let comments = serde_json::Value::from(comments).to_string();
+        // a server ignoring pagination returns the same list again if asked for another page
+        let server = MockServer::start(vec![(200, &comments), (200, &comments), (200, "{}")]);
+        notifier(server.url(), Some(12))
+            .notify(&template(), true)
+            .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, "PATCH");
+        assert_eq!(
+            requests[1].path,
+            "/api/v1/repos/owner/repo/issues/comments/61"
+        );
+    }
+
+    #[test]
+    fn test_notify_without_pull_request_does_nothing() {
+        let server = MockServer::start(vec![]);
+        notifier(server.url(), None)
+            .notify(&template(), true)
+            .unwrap();
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_notify_returns_error_on_failure() {
+        let server = MockServer::start(vec![(401, "{}")]);
+        let actual = notifier(server.url(), Some(12)).notify(&template(), false);
+        assert!(actual.is_err());
+    }
+}
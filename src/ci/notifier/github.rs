@@ -1,12 +1,12 @@
 use crate::ci::MergeRequest;
 use crate::template::Template;
 
+use anyhow::{Context, Result};
 use log::{debug, info};
-use octocrab::{Octocrab, models::issues::Comment};
+use octocrab::{Octocrab, models::CommentId};
 use std::env;
 
-use super::Notifiable;
-use anyhow::{Context, Result};
+use super::{Notifiable, PageComments, post_pages};
 
 #[derive(Debug)]
 pub struct GithubNotifier {
@@ -77,92 +77,79 @@ impl GithubNotifier {
             .context("GITHUB_REPOSITORY must be in <owner>/<repo> format")?;
         Ok((owner.to_string(), repo.to_string()))
     }
+}
+
+impl PageComments for GithubNotifier {
+    /// GitHub rejects comments over 65,536 characters.
+    const MAX_COMMENT_SIZE: Option<usize> = Some(65536);
+
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+        info!("retrieve same build comments");
 
-    #[allow(clippy::collapsible_if)]
-    async fn post_comment(&self, template: &Template, patch: bool) -> Result<()> {
-        if patch {
-            if let Some(same_build_comment) = self.retrieve_same_build_comment(template).await? {
-                let _ = self
-                    .update_existing_comment(template, same_build_comment)
-                    .await;
-                return Ok(());
-            }
+        // get recent 300 comments from the PR
+        let comments = self.runtime.block_on(
+            self.client
+                .issues(&self.owner, &self.repo)
+                .list_comments(number)
+                .send(),
+        )?;
+        if comments.items.is_empty() {
+            debug!("no comments found in the PR");
         }
 
-        let _ = self.create_new_comment(template).await;
-        Ok(())
+        Ok(comments
+            .items
+            .into_iter()
+            .filter_map(|comment| Some((comment.id.into_inner(), comment.body?)))
+            .collect())
     }
 
-    async fn create_new_comment(&self, template: &Template) -> Result<()> {
-        let pr_number = if let Some(n) = self.pull_request.number {
-            n
-        } else {
-            debug!("pull request number is None");
-            return Ok(());
-        };
-
+    fn create_comment(&self, number: u64, body: String) -> Result<()> {
         debug!(
             "create new comment for PR #{}, owner:{}, repo: {}",
-            pr_number, self.owner, self.repo
+            number, self.owner, self.repo
+        );
+        let res = self.runtime.block_on(
+            self.client
+                .issues(&self.owner, &self.repo)
+                .create_comment(number, body),
         );
-        let res = self
-            .client
-            .issues(&self.owner, &self.repo)
-            .create_comment(pr_number, template.render()?)
-            .await;
         debug!("create comment response: {res:?}");
         res?;
         Ok(())
     }
 
-    async fn update_existing_comment(&self, template: &Template, comment: Comment) -> Result<()> {
-        let _ = self
-            .client
-            .issues(&self.owner, &self.repo)
-            .update_comment(comment.id, template.render()?)
-            .await?;
+    fn update_comment(&self, _number: u64, id: u64, body: String) -> Result<()> {
+        self.runtime.block_on(
+            self.client
+                .issues(&self.owner, &self.repo)
+                .update_comment(CommentId(id), body),
+        )?;
         Ok(())
     }
 
-    async fn retrieve_same_build_comment(&self, template: &Template) -> Result<Option<Comment>> {
-        info!("retrieve same build comment");
-
-        let pr_number = if let Some(n) = self.pull_request.number {
-            n
-        } else {
-            debug!("pull request number is None");
-            return Ok(None);
-        };
-
-        // get recent 300 comments from the PR
-        let comments = self
-            .client
-            .issues(&self.owner, &self.repo)
-            .list_comments(pr_number)
-            .send()
-            .await?;
-        if comments.items.is_empty() {
-            debug!("no comments found in the PR");
-            return Ok(None);
-        }
-
-        for comment in comments.items {
-            if let Some(body) = comment.body.clone() {
-                debug!("checking comment: {body}");
-                if template.is_same_build(&body)? {
-                    return Ok(Some(comment));
-                }
-            }
-        }
-        Ok(None)
+    fn delete_comment(&self, _number: u64, id: u64) -> Result<()> {
+        debug!("delete comment {id}");
+        self.runtime.block_on(
+            self.client
+                .issues(&self.owner, &self.repo)
+                .delete_comment(CommentId(id)),
+        )?;
+        Ok(())
     }
 }
 
 impl Notifiable for GithubNotifier {
     fn notify(&self, template: &Template, patch: bool) -> Result<()> {
         info!("notify to GitHub");
-        self.runtime.block_on(self.post_comment(template, patch))?;
-        Ok(())
+
+        let pr_number = if let Some(n) = self.pull_request.number {
+            n
+        } else {
+            debug!("pull request number is None");
+            return Ok(());
+        };
+        post_pages(self, pr_number, template, patch)
     }
 
     fn job_url(&self) -> String {
@@ -3,6 +3,7 @@ use crate::template::Template;
 
 use anyhow::Result;
 use gitlab::Gitlab;
+use gitlab::api::endpoint_prelude::{Cow, Endpoint, Method};
 use gitlab::api::projects::merge_requests::notes::{
     CreateMergeRequestNote, EditMergeRequestNote, MergeRequestNotes,
 };
@@ -12,7 +13,7 @@ use log::info;
 use serde::Deserialize;
 use std::env;
 
-use super::Notifiable;
+use super::{Notifiable, PageComments, post_pages};
 
 const ENV_GITLAB_TOKEN: &str = "KSNOTIFY_GITLAB_TOKEN";
 const LIST_NOTES_LIMIT: usize = 300;
@@ -37,6 +38,27 @@ struct GitLabMergeRequest {
     iid: u64,
 }
 
+/// Delete a note on a merge request, which the gitlab crate does not provide.
+struct DeleteMergeRequestNote {
+    project: u64,
+    merge_request: u64,
+    note: u64,
+}
+
+impl Endpoint for DeleteMergeRequestNote {
+    fn method(&self) -> Method {
+        Method::DELETE
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!(
+            "projects/{}/merge_requests/{}/notes/{}",
+            self.project, self.merge_request, self.note
+        )
+        .into()
+    }
+}
+
 impl GitlabNotifier {
     pub fn new() -> Result<Self> {
         info!("create GitLab client");
@@ -83,25 +105,6 @@ impl GitlabNotifier {
         Ok(env::var("CI_PROJECT_ID")?.parse::<u64>()?)
     }
 
-    fn retrieve_same_build_comment(&self, template: &Template) -> Result<Option<Note>> {
-        info!("retrieve same build comment");
-        let endpoint = MergeRequestNotes::builder()
-            .project(self.project)
-            .merge_request(self.retrieve_merge_request_iid_with_fallback(self.merge_request())?)
-            .build()
-            .map_err(anyhow::Error::msg)?;
-        let comments: Vec<Note> = api::paged(endpoint, api::Pagination::Limit(LIST_NOTES_LIMIT))
-            .query(&self.client)
-            .map_err(anyhow::Error::msg)?;
-
-        for comment in comments {
-            if template.is_same_build(&comment.body)? {
-                return Ok(Some(comment));
-            }
-        }
-        Ok(None)
-    }
-
     /// Retrieve merge request IID with fallback.
     /// If merge request number is not provided, it will retrieve the merge request IID by commit SHA.
     fn retrieve_merge_request_iid_with_fallback(&self, mr: &MergeRequest) -> Result<u64> {
@@ -129,41 +132,65 @@ impl GitlabNotifier {
     }
 }
 
-impl Notifiable for GitlabNotifier {
-    #[allow(clippy::collapsible_if)]
-    fn notify(&self, template: &Template, patch: bool) -> Result<()> {
-        info!("notify to GitLab");
+impl PageComments for GitlabNotifier {
+    /// GitLab rejects notes over 1,000,000 characters.
+    const MAX_COMMENT_SIZE: Option<usize> = Some(1_000_000);
 
-        let same_build_comment = self.retrieve_same_build_comment(template)?;
-
-        // update comment if existed
-        if patch {
-            if let Some(same_build_comment) = same_build_comment {
-                let note = EditMergeRequestNote::builder()
-                    .project(self.project)
-                    .merge_request(
-                        self.retrieve_merge_request_iid_with_fallback(self.merge_request())?,
-                    )
-                    .note(same_build_comment.id)
-                    .body(template.render()?)
-                    .build()
-                    .map_err(anyhow::Error::msg)?;
-                api::ignore(note).query(&self.client)?;
-                return Ok(());
-            }
-        }
+    fn list_comments(&self, number: u64) -> Result<Vec<(u64, String)>> {
+        info!("retrieve same build comments");
+        let endpoint = MergeRequestNotes::builder()
+            .project(self.project)
+            .merge_request(number)
+            .build()
+            .map_err(anyhow::Error::msg)?;
+        let comments: Vec<Note> = api::paged(endpoint, api::Pagination::Limit(LIST_NOTES_LIMIT))
+            .query(&self.client)
+            .map_err(anyhow::Error::msg)?;
+        Ok(comments.into_iter().map(|n| (n.id, n.body)).collect())
+    }
 
-        // create new comment
+    fn create_comment(&self, number: u64, body: String) -> Result<()> {
         let note = CreateMergeRequestNote::builder()
             .project(self.project)
-            .merge_request(self.retrieve_merge_request_iid_with_fallback(self.merge_request())?)
-            .body(template.render()?)
+            .merge_request(number)
+            .body(body)
+            .build()
+            .map_err(anyhow::Error::msg)?;
+        api::ignore(note).query(&self.client)?;
+        Ok(())
+    }
+
+    fn update_comment(&self, number: u64, id: u64, body: String) -> Result<()> {
+        let note = EditMergeRequestNote::builder()
+            .project(self.project)
+            .merge_request(number)
+            .note(id)
+            .body(body)
             .build()
             .map_err(anyhow::Error::msg)?;
         api::ignore(note).query(&self.client)?;
         Ok(())
     }
 
+    fn delete_comment(&self, number: u64, id: u64) -> Result<()> {
+        let note = DeleteMergeRequestNote {
+            project: self.project,
+            merge_request: number,
+            note: id,
+        };
+        api::ignore(note).query(&self.client)?;
+        Ok(())
+    }
+}
+
+impl Notifiable for GitlabNotifier {
+    fn notify(&self, template: &Template, patch: bool) -> Result<()> {
+        info!("notify to GitLab");
+
+        let merge_request = self.retrieve_merge_request_iid_with_fallback(self.merge_request())?;
+        post_pages(self, merge_request, template, patch)
+    }
+
     fn job_url(&self) -> String {
         self.job_url.clone()
     }
@@ -264,6 +291,17 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_delete_merge_request_note_endpoint() {
+        let endpoint = DeleteMergeRequestNote {
+            project: 1,
+            merge_request: 2,
+            note: 3,
+        };
+        assert_eq!(endpoint.method(), Method::DELETE);
+        assert_eq!(endpoint.endpoint(), "projects/1/merge_requests/2/notes/3");
+    }
+
     #[test]
     fn test_get_project_with_invalid_value() {
         temp_env::with_var("CI_PROJECT_ID", Some("not-a-number"), || {
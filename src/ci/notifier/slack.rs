@@ -0,0 +1,101 @@
+use crate::template::Template;
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use reqwest::blocking::Client;
+use std::env;
+
+use super::{Notifiable, job_url_from_env};
+
+const ENV_SLACK_WEBHOOK_URL: &str = "KSNOTIFY_SLACK_WEBHOOK_URL";
+
+/// Notifier posting a summary to a Slack channel through an incoming webhook, e.g. on pipelines of
+/// the main branch. Each run posts a new message, so `--patch` has no effect.
+#[derive(Debug)]
+pub struct SlackNotifier {
+    client: Client,
+    webhook_url: String,
+    job_url: String,
+}
+
+impl SlackNotifier {
+    /// `webhook_url` is from the config file. `KSNOTIFY_SLACK_WEBHOOK_URL` is used if it is not set.
+    pub fn new(webhook_url: Option<String>) -> Result<Self> {
+        info!("create Slack client");
+
+        let webhook_url = webhook_url
+            .or_else(|| env::var(ENV_SLACK_WEBHOOK_URL).ok())
+            .with_context(|| format!("{ENV_SLACK_WEBHOOK_URL} or slack_webhook_url must be set"))?;
+        Ok(Self {
+            client: Client::new(),
+            webhook_url,
+            job_url: job_url_from_env(),
+        })
+    }
+}
+
+impl Notifiable for SlackNotifier {
+    fn notify(&self, template: &Template, _patch: bool) -> Result<()> {
+        info!("notify to Slack");
+
+        let payload = template.render_slack()?;
+        let res = self.client.post(&self.webhook_url).json(&payload).send()?;
+        debug!("webhook response: {res:?}");
+        res.error_for_status()?;
+        Ok(())
+    }
+
+    fn job_url(&self) -> String {
+        self.job_url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::template;
+    use super::super::mock_server::MockServer;
+    use super::*;
+
+    fn notifier(webhook_url: &str) -> SlackNotifier {
+        SlackNotifier {
+            client: Client::new(),
+            webhook_url: format!("{webhook_url}/services/T000/B000/XXXX"),
+            job_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_new_reads_webhook_url_from_config_or_env() {
+        temp_env::with_var(
+            ENV_SLACK_WEBHOOK_URL,
+            Some("https://hooks.slack.com/env"),
+            || {
+                let notifier = SlackNotifier::new(None).unwrap();
+                assert_eq!(notifier.webhook_url, "https://hooks.slack.com/env");
+                let notifier =
+                    SlackNotifier::new(Some("https://hooks.slack.com/config".to_string())).unwrap();
+                assert_eq!(notifier.webhook_url, "https://hooks.slack.com/config");
+            },
+        );
+        temp_env::with_var_unset(ENV_SLACK_WEBHOOK_URL, || {
+            assert!(SlackNotifier::new(None).is_err());
+        });
+    }
+
+    #[test]
+    fn test_notify_posts_blocks() {
+        let server = MockServer::start(vec![(200, "ok")]);
+        notifier(server.url()).notify(&template(), true).unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/services/T000/B000/XXXX");
+        assert_eq!(requests[0].json(), template().render_slack().unwrap());
+    }
+
+    #[test]
+    fn test_notify_returns_error_on_failure() {
+        let server = MockServer::start(vec![(404, "no_service")]);
+        assert!(notifier(server.url()).notify(&template(), false).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::template::{Template, TemplateSource};
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use regex::Regex;
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::sync::LazyLock;
+
+use super::{Notifiable, job_url_from_env};
+
+const ENV_WEBHOOK_URL: &str = "KSNOTIFY_WEBHOOK_URL";
+
+/// Payload posted without a template, e.g. for Mattermost or Teams workflows.
+const DEFAULT_PAYLOAD_TEMPLATE: &str = r#"{"text": {{json comment}}}"#;
+
+// matches a reference to an env var like "${TOKEN}"
+static ENV_REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").unwrap());
+
+/// `webhook` section of the config file.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    /// URL to post to. `KSNOTIFY_WEBHOOK_URL` is used if it is not set.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// HTTP headers. `${NAME}` in the values is replaced with env var `NAME`, e.g. for tokens.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Handlebars template of the JSON payload. `{"text": <comment>}` by default.
+    #[serde(default)]
+    pub template: Option<TemplateSource>,
+}
+
+/// Notifier posting a JSON payload rendered from a user-defined template to any URL, e.g. Teams
+/// Adaptive Cards, Mattermost, Discord or internal services. Each run posts a new payload, so
+/// `--patch` has no effect.
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+    headers: BTreeMap<String, String>,
+    template: String,
+    job_url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &WebhookConfig) -> Result<Self> {
+        info!("create webhook client");
+
+        let url = config
+            .url
+            .clone()
+            .or_else(|| env::var(ENV_WEBHOOK_URL).ok())
+            .with_context(|| format!("{ENV_WEBHOOK_URL} or webhook.url must be set"))?;
+        let headers = config
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), Self::expand_env(value)?)))
+            .collect::<Result<_>>()?;
+        let template = config
+            .template
+            .as_ref()
+            .map(TemplateSource::load)
+            .transpose()?
+            .unwrap_or_else(|| DEFAULT_PAYLOAD_TEMPLATE.to_string());
+        debug!("headers: {:?}", config.headers.keys().collect::<Vec<_>>());
+        Ok(Self {
+            client: Client::new(),
+            url,
+            headers,
+            template,
+            job_url: job_url_from_env(),
+        })
+    }
+
+    /// Replace `${NAME}` in `value` with env var `NAME`.
+    fn expand_env(value: &str) -> Result<String> {
+        let mut missing = None;
+        let expanded = ENV_REFERENCE.replace_all(value, |caps: &regex::Captures| {
+            env::var(&caps[1]).unwrap_or_else(|_| {
+                missing.get_or_insert_with(|| caps[1].to_string());
+                String::new()
+            })
+        });
+        match missing {
+            Some(name) => anyhow::bail!("{name} referred in webhook headers must be set"),
+            None => Ok(expanded.into_owned()),
+        }
+    }
+}
+
+impl Notifiable for WebhookNotifier {
+    fn notify(&self, template: &Template, _patch: bool) -> Result<()> {
+        info!("notify to webhook");
+
+        let payload = template.render_payload(&self.template)?;
+        let mut request = self.client.post(&self.url).json(&payload);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        let res = request.send()?;
+        debug!("webhook response: {res:?}");
+        res.error_for_status()?;
+        Ok(())
+    }
+
+    fn job_url(&self) -> String {
+        self.job_url.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::fixtures::template;
+    use super::super::mock_server::MockServer;
+    use super::*;
+
+    fn notifier(url: &str, template: &str) -> WebhookNotifier {
+        WebhookNotifier {
+            client: Client::new(),
+            url: format!("{url}/hooks/ksnotify"),
+            headers: BTreeMap::from([("X-Api-Key".to_string(), "secret".to_string())]),
+            template: template.to_string(),
+            job_url: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_new() {
+        let config = WebhookConfig {
+            url: None,
+            headers: BTreeMap::from([(
+                "Authorization".to_string(),
+                "Bearer ${WEBHOOK_TOKEN}".to_string(),
+            )]),
+            template: None,
+        };
+        temp_env::with_vars(
+            [
+                (ENV_WEBHOOK_URL, Some("https://example.com/hook")),
+                ("WEBHOOK_TOKEN", Some("xxx")),
+            ],
+            || {
+                let notifier = WebhookNotifier::new(&config).unwrap();
+                assert_eq!(notifier.url, "https://example.com/hook");
+                assert_eq!(notifier.headers["Authorization"], "Bearer xxx");
+                assert_eq!(notifier.template, DEFAULT_PAYLOAD_TEMPLATE);
+            },
+        );
+        temp_env::with_vars(
+            [
+                (ENV_WEBHOOK_URL, Some("https://example.com/hook")),
+                ("WEBHOOK_TOKEN", None),
+            ],
+            || {
+                assert!(WebhookNotifier::new(&config).is_err());
+            },
+        );
+        temp_env::with_var_unset(ENV_WEBHOOK_URL, || {
+            assert!(WebhookNotifier::new(&WebhookConfig::default()).is_err());
+        });
+    }
+
+    #[test]
+    fn test_expand_env() {
+        temp_env::with_var("WEBHOOK_TOKEN", Some("xxx"), || {
+            assert_eq!(
+                WebhookNotifier::expand_env("token ${WEBHOOK_TOKEN} $WEBHOOK_TOKEN").unwrap(),
+                "token xxx $WEBHOOK_TOKEN"
+            );
+        });
+    }
+
+    #[test]
+    fn test_notify_posts_rendered_payload_with_headers() {
+        let server = MockServer::start(vec![(200, "{}")]);
+        notifier(
+            server.url(),
+            r#"{"title": {{json title}}, "count": {{len resources}}}"#,
+        )
+        .notify(&template(), true)
+        .unwrap();
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/hooks/ksnotify");
+        assert_eq!(requests[0].header("x-api-key"), Some("secret"));
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(
+            requests[0].json(),
+            serde_json::json!({"title": "## Plan result (test)", "count": 1})
+        );
+    }
+
+    #[test]
+    fn test_notify_with_invalid_template_does_not_post() {
+        let server = MockServer::start(vec![]);
+        let actual = notifier(server.url(), "{{title}}").notify(&template(), false);
+        assert!(actual.is_err());
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn test_notify_returns_error_on_failure() {
+        let server = MockServer::start(vec![(500, "{}")]);
+        let actual = notifier(server.url(), DEFAULT_PAYLOAD_TEMPLATE).notify(&template(), false);
+        assert!(actual.is_err());
+    }
+}
@@ -1,12 +1,18 @@
 use crate::Cli;
 use crate::ci;
+use crate::parser::{DiffDialect, SuppressPreset, SuppressRule};
+use crate::report::{ColorMode, OutputFormat};
+use crate::resource::ResourceSelector;
+use crate::template::TemplateSource;
 
 use anyhow::Result;
+use itertools::Itertools;
 use log::info;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,6 +22,73 @@ pub struct Config {
     pub suppress_argocd: bool,
     pub ignore_tag_images: Vec<String>,
     pub patch: bool,
+    #[serde(default)]
+    pub diff_dialect: DiffDialect,
+    #[serde(default)]
+    pub semantic_diff: bool,
+    #[serde(default)]
+    pub suppress_presets: Vec<SuppressPreset>,
+    /// Regexes of keys whose values are masked in addition to Secret data.
+    #[serde(default)]
+    pub redact_keys: Vec<String>,
+    /// Resources to report. All resources by default.
+    #[serde(default)]
+    pub include: Vec<ResourceSelector>,
+    /// Resources to list in the ignored section instead of reporting.
+    #[serde(default)]
+    pub exclude: Vec<ResourceSelector>,
+    /// Format of the output written to stdout in addition to the comments. JSON replaces the local
+    /// output, so that stdout stays machine-readable.
+    #[serde(default)]
+    pub output: OutputFormat,
+    /// Whether to print colored text instead of Markdown in local mode.
+    #[serde(default)]
+    pub color: ColorMode,
+    /// Path to write a JUnit XML report with a test case per changed resource.
+    #[serde(default)]
+    pub junit_report: Option<PathBuf>,
+    /// Path to write a SARIF report with a result per changed resource.
+    #[serde(default)]
+    pub sarif_report: Option<PathBuf>,
+    /// Group the resources by namespace and kind in the comment instead of listing them by action.
+    #[serde(default)]
+    pub group_by_namespace: bool,
+    /// Maximum size of a comment in bytes. Larger comments are split into pages at resource
+    /// boundaries. The limit of the platform applies anyway.
+    #[serde(default)]
+    pub max_comment_size: Option<usize>,
+    /// Maximum lines of the diff of a resource. Longer diffs are cut off.
+    #[serde(default)]
+    pub max_lines_per_resource: Option<usize>,
+    /// Maximum size of the diffs in bytes. The biggest diffs are cut off first.
+    #[serde(default)]
+    pub max_total_bytes: Option<usize>,
+    /// User-defined suppression rules. Only available in the config file.
+    #[serde(default)]
+    pub suppress_rules: Vec<SuppressRule>,
+    /// Handlebars template of the comment title. Only available in the config file.
+    #[serde(default)]
+    pub title_template: Option<TemplateSource>,
+    /// Handlebars template of the comment body. Only available in the config file.
+    #[serde(default)]
+    pub body_template: Option<TemplateSource>,
+    /// Handlebars partials shared by the title and the body templates. Only available in the config file.
+    #[serde(default)]
+    pub partials: BTreeMap<String, TemplateSource>,
+    /// Incoming webhook URL of Slack. Only available in the config file, `KSNOTIFY_SLACK_WEBHOOK_URL`
+    /// is read otherwise.
+    #[serde(default)]
+    pub slack_webhook_url: Option<String>,
+    /// URL, headers and payload template of the generic webhook. Only available in the config file.
+    #[serde(default)]
+    pub webhook: ci::WebhookConfig,
+    /// Notifiers to run in addition to `ci`, e.g. `[slack, json]` to post to Slack and print the
+    /// JSON report along with the comment.
+    #[serde(default)]
+    pub notifiers: Vec<ci::CIKind>,
+    /// Whether a failure of a notifier makes the process exit with non-zero status.
+    #[serde(default)]
+    pub on_notifier_failure: ci::FailurePolicy,
 }
 
 impl Config {
@@ -29,12 +102,50 @@ impl Config {
             let suppress_argocd = cli.suppress_argocd;
             let ignore_tag_images = cli.ignore_tag_images.clone();
             let patch = cli.patch;
+            let diff_dialect = cli.diff_dialect;
+            let semantic_diff = cli.semantic_diff;
+            let suppress_presets = cli.suppress_preset.clone();
+            let redact_keys = cli.redact_keys.clone();
+            let include = cli.include.clone();
+            let exclude = cli.exclude.clone();
+            let output = cli.output;
+            let color = cli.color;
+            let junit_report = cli.junit_report.clone();
+            let sarif_report = cli.sarif_report.clone();
+            let group_by_namespace = cli.group_by_namespace;
+            let max_comment_size = cli.max_comment_size;
+            let max_lines_per_resource = cli.max_lines_per_resource;
+            let max_total_bytes = cli.max_total_bytes;
+            let notifiers = cli.notifiers.clone();
+            let on_notifier_failure = cli.on_notifier_failure;
             return Ok(Self {
                 ci,
                 suppress_skaffold,
                 suppress_argocd,
                 ignore_tag_images,
                 patch,
+                diff_dialect,
+                semantic_diff,
+                suppress_presets,
+                redact_keys,
+                include,
+                exclude,
+                output,
+                color,
+                junit_report,
+                sarif_report,
+                group_by_namespace,
+                max_comment_size,
+                max_lines_per_resource,
+                max_total_bytes,
+                suppress_rules: Vec::new(),
+                title_template: None,
+                body_template: None,
+                partials: BTreeMap::new(),
+                slack_webhook_url: None,
+                webhook: ci::WebhookConfig::default(),
+                notifiers,
+                on_notifier_failure,
             });
         }
 
@@ -45,9 +156,19 @@ impl Config {
 
     fn from_file(path: PathBuf) -> Result<Self> {
         info!("cli arguments are not set, use configuration file");
-        let config_string = fs::read_to_string(path)?;
-        let config: Self = serde_yml::from_str(&config_string)?;
+        let config_string = fs::read_to_string(&path)?;
+        let mut config: Self = serde_yml::from_str(&config_string)?;
 
+        // template files are relative to the config file
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+        config.title_template = config.title_template.map(|t| t.relative_to(base_dir));
+        config.body_template = config.body_template.map(|t| t.relative_to(base_dir));
+        config.partials = config
+            .partials
+            .into_iter()
+            .map(|(name, t)| (name, t.relative_to(base_dir)))
+            .collect();
+        config.webhook.template = config.webhook.template.map(|t| t.relative_to(base_dir));
         Ok(config)
     }
 
@@ -63,20 +184,113 @@ impl Config {
             .filter(|s| !s.is_empty())
             .collect();
         let patch = env::var("KSNOTIFY_PATCH").is_ok();
+        let diff_dialect = env::var("KSNOTIFY_DIFF_DIALECT")
+            .ok()
+            .map(|v| DiffDialect::from_str(&v))
+            .transpose()?
+            .unwrap_or_default();
+        let semantic_diff = env::var("KSNOTIFY_SEMANTIC_DIFF").is_ok();
+        let suppress_presets = env::var("KSNOTIFY_SUPPRESS_PRESET")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(SuppressPreset::from_str)
+            .collect::<Result<_, _>>()?;
+        let redact_keys = env::var("KSNOTIFY_REDACT_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .map(String::from)
+            .filter(|s| !s.is_empty())
+            .collect();
+        let include = Self::selectors_from_env("KSNOTIFY_INCLUDE")?;
+        let exclude = Self::selectors_from_env("KSNOTIFY_EXCLUDE")?;
+        let output = env::var("KSNOTIFY_OUTPUT")
+            .ok()
+            .map(|v| OutputFormat::from_str(&v))
+            .transpose()?
+            .unwrap_or_default();
+        let color = env::var("KSNOTIFY_COLOR")
+            .ok()
+            .map(|v| ColorMode::from_str(&v))
+            .transpose()?
+            .unwrap_or_default();
+        let junit_report = env::var("KSNOTIFY_JUNIT_REPORT").ok().map(PathBuf::from);
+        let sarif_report = env::var("KSNOTIFY_SARIF_REPORT").ok().map(PathBuf::from);
+        let group_by_namespace = env::var("KSNOTIFY_GROUP_BY_NAMESPACE").is_ok();
+        let max_comment_size = env::var("KSNOTIFY_MAX_COMMENT_SIZE")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()?;
+        let max_lines_per_resource = env::var("KSNOTIFY_MAX_LINES_PER_RESOURCE")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()?;
+        let max_total_bytes = env::var("KSNOTIFY_MAX_TOTAL_BYTES")
+            .ok()
+            .map(|v| v.parse())
+            .transpose()?;
+        let notifiers = env::var("KSNOTIFY_NOTIFIERS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(ci::CIKind::from_str)
+            .collect::<Result<_, _>>()?;
+        let on_notifier_failure = env::var("KSNOTIFY_ON_NOTIFIER_FAILURE")
+            .ok()
+            .map(|v| ci::FailurePolicy::from_str(&v))
+            .transpose()?
+            .unwrap_or_default();
         Ok(Self {
             ci,
             suppress_skaffold,
             suppress_argocd,
             ignore_tag_images,
             patch,
+            diff_dialect,
+            semantic_diff,
+            suppress_presets,
+            redact_keys,
+            include,
+            exclude,
+            output,
+            color,
+            junit_report,
+            sarif_report,
+            group_by_namespace,
+            max_comment_size,
+            max_lines_per_resource,
+            max_total_bytes,
+            suppress_rules: Vec::new(),
+            title_template: None,
+            body_template: None,
+            partials: BTreeMap::new(),
+            slack_webhook_url: None,
+            webhook: ci::WebhookConfig::default(),
+            notifiers,
+            on_notifier_failure,
         })
     }
+
+    /// `ci` followed by the other notifiers without duplicates.
+    pub fn notifier_kinds(&self) -> Vec<ci::CIKind> {
+        std::iter::once(self.ci)
+            .chain(self.notifiers.iter().copied())
+            .unique()
+            .collect()
+    }
+
+    /// Read whitespace separated selectors like `kind=Secret namespace=kube-*` from env var `key`.
+    fn selectors_from_env(key: &str) -> Result<Vec<ResourceSelector>> {
+        env::var(key)
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(ResourceSelector::from_str)
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use clap_verbosity_flag::{ErrorLevel, Verbosity};
-
     use super::*;
 
     #[test]
@@ -97,7 +311,7 @@ mod tests {
                     ignore_tag_images: vec![],
                     patch: false,
                     config: None,
-                    verbose: Verbosity::<ErrorLevel>::default(),
+                    ..Default::default()
                 })
                 .unwrap();
 
@@ -133,7 +347,7 @@ patch: false
             ignore_tag_images: vec![],
             patch: false,
             config: Some(config_path),
-            verbose: Verbosity::<ErrorLevel>::default(),
+            ..Default::default()
         })
         .unwrap();
 
@@ -142,6 +356,679 @@ patch: false
         assert!(!config.suppress_argocd);
         assert_eq!(config.ignore_tag_images, vec!["image1", "image2"]);
         assert!(!config.patch);
+        assert_eq!(config.diff_dialect, DiffDialect::Auto);
+    }
+
+    #[test]
+    fn test_new_from_file_with_diff_dialect() {
+        let config_content = r#"
+ci: gitlab
+suppress_skaffold: false
+suppress_argocd: false
+ignore_tag_images: []
+patch: false
+diff_dialect: git
+"#;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(config.diff_dialect, DiffDialect::Git);
+        assert!(!config.semantic_diff);
+    }
+
+    #[test]
+    fn test_new_from_file_with_suppress_rules() {
+        let config_content = r#"
+ci: gitlab
+suppress_skaffold: false
+suppress_argocd: false
+ignore_tag_images: []
+patch: false
+suppress_rules:
+  - name: helm-chart
+    line: "helm.sh/chart: "
+  - name: last-applied
+    path: metadata.annotations."kubectl.kubernetes.io/last-applied-configuration"
+    selector:
+      kind: Deployment
+      namespace: default
+    drop: false
+"#;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(config.suppress_rules.len(), 2);
+        let helm = &config.suppress_rules[0];
+        assert_eq!(helm.name, "helm-chart");
+        assert_eq!(helm.line.as_deref(), Some("helm.sh/chart: "));
+        assert_eq!(helm.selector, Default::default());
+        assert!(helm.drop);
+        let last_applied = &config.suppress_rules[1];
+        assert_eq!(
+            last_applied.path.as_deref(),
+            Some(r#"metadata.annotations."kubectl.kubernetes.io/last-applied-configuration""#)
+        );
+        assert_eq!(last_applied.selector.kind.as_deref(), Some("Deployment"));
+        assert_eq!(last_applied.selector.name, None);
+        assert!(!last_applied.drop);
+    }
+
+    #[test]
+    fn test_new_suppress_presets_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nsuppress_presets: [helm, istio]\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            config.suppress_presets,
+            vec![SuppressPreset::Helm, SuppressPreset::Istio]
+        );
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            suppress_preset: vec![SuppressPreset::Flux],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.suppress_presets, vec![SuppressPreset::Flux]);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_SUPPRESS_PRESET", Some("kustomize,flux")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(
+                    config.suppress_presets,
+                    vec![SuppressPreset::Kustomize, SuppressPreset::Flux]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_from_env_with_invalid_suppress_preset_returns_error() {
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_SUPPRESS_PRESET", Some("helm,unknown")),
+            ],
+            || {
+                assert!(Config::new(&Cli::default()).is_err());
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_include_and_exclude_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nexclude:\n  - kind: Secret\n  - namespace: kube-system\n    name: \"*-canary\"\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(config.include.is_empty());
+        assert_eq!(
+            config.exclude,
+            vec![
+                ResourceSelector::from_str("kind=Secret").unwrap(),
+                ResourceSelector::from_str("namespace=kube-system,name=*-canary").unwrap(),
+            ]
+        );
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            include: vec![ResourceSelector::from_str("kind=Deployment").unwrap()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            config.include,
+            vec![ResourceSelector::from_str("kind=Deployment").unwrap()]
+        );
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_EXCLUDE", Some("kind=Secret name=*-canary")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.exclude.len(), 2);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_from_env_with_invalid_selector_returns_error() {
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_INCLUDE", Some("Secret")),
+            ],
+            || {
+                assert!(Config::new(&Cli::default()).is_err());
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_redact_keys_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nredact_keys: [password, \"^token$\"]\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.redact_keys, vec!["password", "^token$"]);
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            redact_keys: vec!["secret".to_string()],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.redact_keys, vec!["secret"]);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_REDACT_KEYS", Some("password,token")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.redact_keys, vec!["password", "token"]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_from_file_with_templates() {
+        let config_content = r#"
+ci: gitlab
+suppress_skaffold: false
+suppress_argocd: false
+ignore_tag_images: []
+patch: false
+title_template: "Diff of {{target}}"
+body_template:
+  file: templates/body.hbs
+partials:
+  footer: "See the runbook"
+  header:
+    file: /etc/ksnotify/header.hbs
+"#;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(
+            config.title_template,
+            Some(TemplateSource::Inline("Diff of {{target}}".to_string()))
+        );
+        assert_eq!(
+            config.body_template,
+            Some(TemplateSource::File {
+                file: temp_dir.path().join("templates/body.hbs")
+            })
+        );
+        assert_eq!(
+            config.partials["footer"],
+            TemplateSource::Inline("See the runbook".to_string())
+        );
+        assert_eq!(
+            config.partials["header"],
+            TemplateSource::File {
+                file: PathBuf::from("/etc/ksnotify/header.hbs")
+            }
+        );
+    }
+
+    #[test]
+    fn test_new_from_file_with_slack_webhook_url() {
+        let config_content = r#"
+ci: slack
+suppress_skaffold: false
+suppress_argocd: false
+ignore_tag_images: []
+patch: false
+slack_webhook_url: https://hooks.slack.com/services/T000/B000/XXXX
+"#;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(config.ci, ci::CIKind::Slack);
+        assert_eq!(
+            config.slack_webhook_url.as_deref(),
+            Some("https://hooks.slack.com/services/T000/B000/XXXX")
+        );
+    }
+
+    #[test]
+    fn test_new_from_file_with_webhook() {
+        let config_content = r#"
+ci: webhook
+suppress_skaffold: false
+suppress_argocd: false
+ignore_tag_images: []
+patch: false
+webhook:
+  url: https://example.com/hooks/ksnotify
+  headers:
+    Authorization: Bearer ${WEBHOOK_TOKEN}
+  template:
+    file: teams.hbs
+"#;
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(&config_path, config_content).unwrap();
+
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(config.ci, ci::CIKind::Webhook);
+        assert_eq!(
+            config.webhook,
+            ci::WebhookConfig {
+                url: Some("https://example.com/hooks/ksnotify".to_string()),
+                headers: BTreeMap::from([(
+                    "Authorization".to_string(),
+                    "Bearer ${WEBHOOK_TOKEN}".to_string()
+                )]),
+                template: Some(TemplateSource::File {
+                    file: temp_dir.path().join("teams.hbs")
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn test_new_semantic_diff_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nsemantic_diff: true\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(config.semantic_diff);
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            semantic_diff: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(config.semantic_diff);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_SEMANTIC_DIFF", Some("true")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert!(config.semantic_diff);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_output_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: json\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\noutput: json\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.ci, ci::CIKind::Json);
+        assert_eq!(config.output, OutputFormat::Json);
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            output: OutputFormat::Json,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.output, OutputFormat::Json);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_OUTPUT", Some("yaml")),
+            ],
+            || {
+                assert!(Config::new(&Cli::default()).is_err());
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_color_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: local\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\ncolor: never\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.color, ColorMode::Never);
+
+        let config = Config::new(&Cli {
+            ci: Some("local".to_string()),
+            color: ColorMode::Always,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.color, ColorMode::Always);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("local")),
+                ("KSNOTIFY_COLOR", Some("always")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.color, ColorMode::Always);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_reports_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\njunit_report: junit.xml\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.junit_report, Some(PathBuf::from("junit.xml")));
+        assert_eq!(config.sarif_report, None);
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            sarif_report: Some(PathBuf::from("ksnotify.sarif")),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.sarif_report, Some(PathBuf::from("ksnotify.sarif")));
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_JUNIT_REPORT", Some("report/junit.xml")),
+                ("KSNOTIFY_SARIF_REPORT", Some("report/ksnotify.sarif")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.junit_report, Some(PathBuf::from("report/junit.xml")));
+                assert_eq!(
+                    config.sarif_report,
+                    Some(PathBuf::from("report/ksnotify.sarif"))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_group_by_namespace_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\ngroup_by_namespace: true\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(config.group_by_namespace);
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            group_by_namespace: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(config.group_by_namespace);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_GROUP_BY_NAMESPACE", Some("true")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert!(config.group_by_namespace);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_max_comment_size_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nmax_comment_size: 30000\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.max_comment_size, Some(30000));
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            max_comment_size: Some(20000),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.max_comment_size, Some(20000));
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_MAX_COMMENT_SIZE", Some("10000")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.max_comment_size, Some(10000));
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_notifiers_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nnotifiers: [slack, json, gitlab]\non_notifier_failure: warn\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.on_notifier_failure, ci::FailurePolicy::Warn);
+        assert_eq!(
+            config.notifier_kinds(),
+            vec![ci::CIKind::GitLab, ci::CIKind::Slack, ci::CIKind::Json]
+        );
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            notifiers: vec![ci::CIKind::Webhook],
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.on_notifier_failure, ci::FailurePolicy::Fail);
+        assert_eq!(
+            config.notifier_kinds(),
+            vec![ci::CIKind::GitHub, ci::CIKind::Webhook]
+        );
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_NOTIFIERS", Some("slack,json")),
+                ("KSNOTIFY_ON_NOTIFIER_FAILURE", Some("warn")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.notifiers, vec![ci::CIKind::Slack, ci::CIKind::Json]);
+                assert_eq!(config.on_notifier_failure, ci::FailurePolicy::Warn);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_truncation_from_each_source() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config_path = temp_dir.path().join("config.yaml");
+        fs::write(
+            &config_path,
+            "ci: gitlab\nsuppress_skaffold: false\nsuppress_argocd: false\nignore_tag_images: []\npatch: false\nmax_lines_per_resource: 100\nmax_total_bytes: 40000\n",
+        )
+        .unwrap();
+        let config = Config::new(&Cli {
+            config: Some(config_path),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.max_lines_per_resource, Some(100));
+        assert_eq!(config.max_total_bytes, Some(40000));
+
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            max_lines_per_resource: Some(50),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(config.max_lines_per_resource, Some(50));
+        assert_eq!(config.max_total_bytes, None);
+
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_MAX_LINES_PER_RESOURCE", Some("10")),
+                ("KSNOTIFY_MAX_TOTAL_BYTES", Some("not-a-number")),
+            ],
+            || {
+                assert!(Config::new(&Cli::default()).is_err());
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_from_env_with_diff_dialect() {
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_DIFF_DIALECT", Some("ansi")),
+            ],
+            || {
+                let config = Config::new(&Cli::default()).unwrap();
+                assert_eq!(config.diff_dialect, DiffDialect::Ansi);
+            },
+        );
+    }
+
+    #[test]
+    fn test_new_from_env_with_invalid_diff_dialect_returns_error() {
+        temp_env::with_vars(
+            [
+                ("KSNOTIFY_CI", Some("github")),
+                ("KSNOTIFY_DIFF_DIALECT", Some("dyff")),
+            ],
+            || {
+                assert!(Config::new(&Cli::default()).is_err());
+            },
+        );
     }
 
     #[test]
@@ -154,7 +1041,7 @@ patch: false
             ignore_tag_images: vec!["image1".to_string(), "image2".to_string()],
             patch: true,
             config: None,
-            verbose: Verbosity::<ErrorLevel>::default(),
+            ..Default::default()
         })
         .unwrap();
 
@@ -165,6 +1052,18 @@ patch: false
         assert!(config.patch);
     }
 
+    #[test]
+    fn test_new_with_cli_diff_dialect() {
+        let config = Config::new(&Cli {
+            ci: Some("github".to_string()),
+            diff_dialect: DiffDialect::Unified,
+            ..Default::default()
+        })
+        .unwrap();
+
+        assert_eq!(config.diff_dialect, DiffDialect::Unified);
+    }
+
     #[test]
     fn test_new_from_env_suppress_argocd_is_independent_of_skaffold() {
         // Set only SUPPRESS_ARGOCD to ensure the two flags are not swapped or conflated.
@@ -183,7 +1082,7 @@ patch: false
                     ignore_tag_images: vec![],
                     patch: false,
                     config: None,
-                    verbose: Verbosity::<ErrorLevel>::default(),
+                    ..Default::default()
                 })
                 .unwrap();
 
@@ -209,7 +1108,7 @@ patch: false
                     ignore_tag_images: vec![],
                     patch: false,
                     config: None,
-                    verbose: Verbosity::<ErrorLevel>::default(),
+                    ..Default::default()
                 })
                 .unwrap();
 
@@ -234,7 +1133,7 @@ patch: false
                     ignore_tag_images: vec![],
                     patch: false,
                     config: None,
-                    verbose: Verbosity::<ErrorLevel>::default(),
+                    ..Default::default()
                 })
                 .unwrap();
 
@@ -253,7 +1152,7 @@ patch: false
             ignore_tag_images: vec![],
             patch: false,
             config: None,
-            verbose: Verbosity::<ErrorLevel>::default(),
+            ..Default::default()
         });
         assert!(result.is_err());
     }
@@ -271,7 +1170,7 @@ patch: false
             ignore_tag_images: vec![],
             patch: false,
             config: Some(missing_path),
-            verbose: Verbosity::<ErrorLevel>::default(),
+            ..Default::default()
         });
         assert!(result.is_err());
     }
@@ -291,7 +1190,7 @@ patch: false
             ignore_tag_images: vec![],
             patch: false,
             config: Some(config_path),
-            verbose: Verbosity::<ErrorLevel>::default(),
+            ..Default::default()
         });
         assert!(result.is_err());
     }
@@ -312,7 +1211,7 @@ patch: false
                     ignore_tag_images: vec![],
                     patch: false,
                     config: None,
-                    verbose: Verbosity::<ErrorLevel>::default(),
+                    ..Default::default()
                 });
                 assert!(result.is_err());
             },
@@ -1,17 +1,21 @@
 mod ci;
 mod config;
+mod manifest;
 mod parser;
+mod redaction;
+mod report;
+mod resource;
+mod semantic;
 mod template;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use clap_verbosity_flag::Verbosity;
 use log::{debug, error, info};
 use parser::Parsable;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Read};
 use std::path::PathBuf;
 use std::process;
-use std::string::ToString;
 
 fn get_version() -> &'static str {
     option_env!("CARGO_PKG_VERSION")
@@ -20,7 +24,7 @@ fn get_version() -> &'static str {
         .unwrap_or("unknown")
 }
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(author, version = get_version(), about, long_about = None)]
 pub struct Cli {
     /// CI platform name.
@@ -43,6 +47,62 @@ pub struct Cli {
     #[arg(long)]
     pub suppress_argocd: bool,
 
+    /// Presets of labels and annotations to suppress (helm, flux, kustomize or istio).
+    #[arg(long, value_delimiter = ',')]
+    pub suppress_preset: Vec<parser::SuppressPreset>,
+
+    /// Regexes of keys (e.g. `password,token`) whose values are masked in addition to Secret data. Env vars are matched by their names.
+    #[arg(long, value_delimiter = ',')]
+    pub redact_keys: Vec<String>,
+
+    /// Report only resources matching the selector like `kind=Deployment,namespace=prod-*`. Can be given multiple times.
+    #[arg(long, value_name = "SELECTOR")]
+    pub include: Vec<resource::ResourceSelector>,
+
+    /// List resources matching the selector like `name=*-canary` in the ignored section instead of reporting. Can be given multiple times.
+    #[arg(long, value_name = "SELECTOR")]
+    pub exclude: Vec<resource::ResourceSelector>,
+
+    /// Format of the output written to stdout (markdown or json). With json, the parse result is printed in JSON in addition to the comments posted by the notifiers, and instead of the local output.
+    #[arg(long, default_value = "markdown")]
+    pub output: report::OutputFormat,
+
+    /// Print colored text instead of Markdown in local mode (auto, always or never). With auto, colored text is printed if stdout is a terminal.
+    #[arg(long, default_value = "auto")]
+    pub color: report::ColorMode,
+
+    /// Path to write a JUnit XML report with a failed test case per changed resource.
+    #[arg(long, value_name = "FILE")]
+    pub junit_report: Option<PathBuf>,
+
+    /// Path to write a SARIF report with a result per changed resource.
+    #[arg(long, value_name = "FILE")]
+    pub sarif_report: Option<PathBuf>,
+
+    /// Group the resources by namespace and then by kind in collapsible sections instead of listing them by action.
+    #[arg(long)]
+    pub group_by_namespace: bool,
+
+    /// Maximum size of a comment in bytes. Larger comments are split into pages at resource boundaries.
+    #[arg(long, value_name = "BYTES")]
+    pub max_comment_size: Option<usize>,
+
+    /// Maximum lines of the diff of a resource. Longer diffs are cut off with a summary of the elided lines.
+    #[arg(long, value_name = "LINES")]
+    pub max_lines_per_resource: Option<usize>,
+
+    /// Maximum size of the diffs in bytes. The biggest diffs are cut off first.
+    #[arg(long, value_name = "BYTES")]
+    pub max_total_bytes: Option<usize>,
+
+    /// Notifiers to run in addition to `--ci` (e.g. `slack,json`). A failing notifier does not stop the others.
+    #[arg(long, value_delimiter = ',')]
+    pub notifiers: Vec<ci::CIKind>,
+
+    /// Whether a failure of a notifier makes the process exit with non-zero status (fail or warn).
+    #[arg(long, default_value = "fail")]
+    pub on_notifier_failure: ci::FailurePolicy,
+
     /// Path of config file in YAML format. This option cannot conjunction with ci and notifier options.
     #[arg(long, value_name = "FILE")]
     pub config: Option<PathBuf>,
@@ -51,8 +111,37 @@ pub struct Cli {
     #[arg(long, value_delimiter = ',')]
     pub ignore_tag_images: Vec<String>,
 
+    /// Format of the diff given by `kubectl diff` (auto, unified, git or ansi). It depends on KUBECTL_EXTERNAL_DIFF.
+    #[arg(long, default_value = "auto")]
+    pub diff_dialect: parser::DiffDialect,
+
+    /// Report path-based changes (e.g. `spec.replicas: 1 -> 2`) of configured resources instead of line diffs when the whole documents are available.
+    #[arg(long)]
+    pub semantic_diff: bool,
+
     #[clap(flatten)]
     verbose: Verbosity,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Compute the diff from two manifest files instead of reading `kubectl diff` result from stdin.
+    Diff {
+        /// Manifests currently applied to the cluster (e.g. `kubectl get -o yaml` result).
+        #[arg(long, value_name = "FILE")]
+        live: PathBuf,
+
+        /// Manifests to be applied.
+        #[arg(long, value_name = "FILE")]
+        desired: PathBuf,
+
+        /// Namespace of the namespaced objects without `metadata.namespace`, like `kubectl apply -n`.
+        #[arg(long, default_value = "default")]
+        namespace: String,
+    },
 }
 
 fn main() {
@@ -80,36 +169,92 @@ fn run() -> Result<()> {
         config::Config::new(&cli).with_context(|| format!("failed to load config: {cli:?}"))?;
     info!("config: {config:?}");
 
-    // Local PC (for debug)
-    if config.ci == ci::CIKind::Local {
-        let content = process(&config, None, cli.target)?;
-        println!("{}", content.render()?);
-        return Ok(());
+    let body = read_diff(cli.command.as_ref(), config.semantic_diff)?;
+
+    let kinds = config.notifier_kinds();
+    let mut notifiers = ci::Notifiers::new(&kinds, &config);
+    let (template, report) = process(&config, &body, notifiers.job_url(), cli.target)?;
+    if kinds.contains(&ci::CIKind::Json) || config.output == report::OutputFormat::Json {
+        println!("{}", report.to_json()?);
+    } else if kinds.contains(&ci::CIKind::Local) {
+        // Local PC (for debug)
+        let is_terminal = io::stdout().is_terminal();
+        if config.color == report::ColorMode::Auto && !is_terminal {
+            println!("{}", template.render_pages(None)?.join("\n"));
+        } else {
+            let color = config.color == report::ColorMode::Always
+                || (config.color == report::ColorMode::Auto && is_terminal);
+            print!("{}", report.to_terminal(color));
+        }
     }
+    notifiers.notify(&template, config.patch);
+    // written after notifying, so that an unwritable report does not hold back the comments
+    let written = report.write_files(
+        config.junit_report.as_deref(),
+        config.sarif_report.as_deref(),
+    );
+    let finished = notifiers.finish(config.on_notifier_failure);
+    written.context("failed to write reports").and(finished)
+}
 
-    let ci =
-        ci::CI::new(config.ci).with_context(|| format!("failed to create CI: {:?}", config.ci))?;
-    let template = process(&config, Some(ci.job_url()), cli.target)?;
-    ci.notifier
-        .notify(&template, config.patch)
-        .with_context(|| "failed to notify".to_string())?;
-    Ok(())
+/// Read `kubectl diff` result from stdin, or compute it from manifest files with `diff` subcommand.
+/// The manifests are diffed with full context in the semantic diff mode to reconstruct the documents.
+fn read_diff(command: Option<&Command>, full_context: bool) -> Result<String> {
+    if let Some(Command::Diff {
+        live,
+        desired,
+        namespace,
+    }) = command
+    {
+        return manifest::diff_files(live, desired, namespace, full_context);
+    }
+    let mut body = String::new();
+    io::stdin().read_to_string(&mut body)?;
+    Ok(body)
 }
 
 fn process(
     config: &config::Config,
+    body: &str,
     url: Option<String>,
     target: Option<String>,
-) -> Result<template::Template> {
-    let mut body = String::new();
-    io::stdin().read_to_string(&mut body)?;
+) -> Result<(template::Template, report::Report)> {
     let parser = parser::DiffParser::new(
         config.suppress_skaffold,
         config.suppress_argocd,
         config.ignore_tag_images.clone(),
-    )?;
-    let result = parser.parse(&body)?;
+    )?
+    .with_dialect(config.diff_dialect)
+    .with_redact_keys(&config.redact_keys)?
+    .with_suppress_presets(&config.suppress_presets)?
+    .with_suppress_rules(&config.suppress_rules)?
+    .with_semantic_diff(config.semantic_diff);
+    let result = parser.parse(body)?;
+    let (resources, ignored) =
+        resource::ResourceSelector::filter(result.resources, &config.include, &config.exclude);
     let link = url.unwrap_or_default();
-    let template = template::Template::new(result.kind_result, link, target);
-    Ok(template)
+    let report = report::Report::new(&resources, &ignored, link.clone(), target.clone());
+    let title_template = config
+        .title_template
+        .as_ref()
+        .map(template::TemplateSource::load)
+        .transpose()?;
+    let body_template = config
+        .body_template
+        .as_ref()
+        .map(template::TemplateSource::load)
+        .transpose()?;
+    let partials = config
+        .partials
+        .iter()
+        .map(|(name, partial)| Ok((name.clone(), partial.load()?)))
+        .collect::<Result<_>>()?;
+    let template = template::Template::new(resources, link, target)
+        .with_ignored(&ignored)
+        .with_templates(title_template, body_template)
+        .with_partials(partials)
+        .with_grouped_layout(config.group_by_namespace)
+        .with_truncation(config.max_lines_per_resource, config.max_total_bytes)
+        .with_max_comment_size(config.max_comment_size);
+    Ok((template, report))
 }
@@ -0,0 +1,363 @@
+use anyhow::{Context, Result, anyhow};
+use log::debug;
+use regex::Regex;
+use serde_yml::Value;
+use similar::TextDiff;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+// matches YAML document separator line like "---" or "--- # comment"
+static DOCUMENT_SEPARATOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^---[ \t]*(#.*)?\r?$").unwrap());
+
+/// Metadata fields the API server sets on live objects. They are never in the desired
+/// manifests, so they are dropped from both sides to keep the diff to what the user changes.
+const SERVER_MANAGED_METADATA: [&str; 6] = [
+    "creationTimestamp",
+    "generation",
+    "managedFields",
+    "resourceVersion",
+    "selfLink",
+    "uid",
+];
+
+/// Built-in kinds which are not namespaced, so that they are not given the default namespace.
+const CLUSTER_SCOPED_KINDS: [&str; 22] = [
+    "APIService",
+    "CSIDriver",
+    "CSINode",
+    "CertificateSigningRequest",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "ComponentStatus",
+    "CustomResourceDefinition",
+    "FlowSchema",
+    "IngressClass",
+    "MutatingWebhookConfiguration",
+    "Namespace",
+    "Node",
+    "PersistentVolume",
+    "PriorityClass",
+    "PriorityLevelConfiguration",
+    "RuntimeClass",
+    "StorageClass",
+    "ValidatingAdmissionPolicy",
+    "ValidatingAdmissionPolicyBinding",
+    "ValidatingWebhookConfiguration",
+    "VolumeAttachment",
+];
+
+/// Number of context lines in each hunk, same as `diff -u`.
+const CONTEXT_LINES: usize = 3;
+
+/// Compute the diff between the live and the desired manifests in the same format as
+/// `kubectl diff`, so that it can be parsed by `DiffParser`.
+/// Namespaced objects without namespace are put in `namespace` as `kubectl apply` does.
+/// With `full_context`, each object is diffed as a single hunk covering the whole document.
+pub fn diff_files(
+    live: &Path,
+    desired: &Path,
+    namespace: &str,
+    full_context: bool,
+) -> Result<String> {
+    let live_manifest = fs::read_to_string(live)
+        .with_context(|| format!("failed to read live manifests: {}", live.display()))?;
+    let desired_manifest = fs::read_to_string(desired)
+        .with_context(|| format!("failed to read desired manifests: {}", desired.display()))?;
+    diff(&live_manifest, &desired_manifest, namespace, full_context)
+}
+
+/// Compute the diff between two multi-document YAML strings.
+/// Objects are matched by apiVersion, kind, namespace and name.
+pub fn diff(live: &str, desired: &str, namespace: &str, full_context: bool) -> Result<String> {
+    let live = load(live, namespace).context("failed to load live manifests")?;
+    let desired = load(desired, namespace).context("failed to load desired manifests")?;
+
+    let names: BTreeSet<&String> = live.keys().chain(desired.keys()).collect();
+
+    let mut result = String::new();
+    let mut changed = Vec::new();
+    for name in names {
+        let old = live.get(name).map_or("", String::as_str);
+        let new = desired.get(name).map_or("", String::as_str);
+        if old == new {
+            continue;
+        }
+        changed.push(name.as_str());
+        let old_path = format!("/tmp/LIVE/{name}");
+        let new_path = format!("/tmp/MERGED/{name}");
+        let context_lines = if full_context {
+            old.lines().count().max(new.lines().count())
+        } else {
+            CONTEXT_LINES
+        };
+        let diff = TextDiff::from_lines(old, new);
+        result.push_str(&format!("diff -u -N {old_path} {new_path}\n"));
+        result.push_str(
+            &diff
+                .unified_diff()
+                .context_radius(context_lines)
+                .header(&old_path, &new_path)
+                .to_string(),
+        );
+    }
+    // only the names, as the diff is not redacted yet
+    debug!("changed objects: {changed:?}");
+    Ok(result)
+}
+
+/// Load manifests keyed by the file name `kubectl diff` would use for them.
+fn load(manifest: &str, namespace: &str) -> Result<BTreeMap<String, String>> {
+    let mut objects = BTreeMap::new();
+    for document in DOCUMENT_SEPARATOR.split(manifest) {
+        if document.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_yml::from_str(document)?;
+        for object in flatten(value) {
+            let (name, body) = normalize(object, namespace)?;
+            if objects.insert(name.clone(), body).is_some() {
+                return Err(anyhow!("duplicated object: {name}"));
+            }
+        }
+    }
+    Ok(objects)
+}
+
+/// Expand `kind: List` (e.g. `kubectl get -o yaml` output) into its items.
+fn flatten(value: Value) -> Vec<Value> {
+    if value.is_null() {
+        return Vec::new();
+    }
+    if value.get("kind").and_then(Value::as_str) == Some("List") {
+        return value
+            .get("items")
+            .and_then(Value::as_sequence)
+            .map_or_else(Vec::new, |items| {
+                items.iter().cloned().flat_map(flatten).collect()
+            });
+    }
+    vec![value]
+}
+
+/// Return the `[group.]version.kind.namespace.name` file name and the YAML text of an object.
+/// A namespaced object without namespace is put in `default_namespace`.
+fn normalize(mut object: Value, default_namespace: &str) -> Result<(String, String)> {
+    let field = |object: &Value, path: &str| -> Option<String> {
+        object
+            .get_path(path)
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+    let api_version =
+        field(&object, "apiVersion").ok_or_else(|| anyhow!("object without apiVersion"))?;
+    let kind = field(&object, "kind").ok_or_else(|| anyhow!("object without kind"))?;
+    let name =
+        field(&object, "metadata.name").ok_or_else(|| anyhow!("{kind} without metadata.name"))?;
+    let namespace = match field(&object, "metadata.namespace") {
+        Some(namespace) => namespace,
+        None if CLUSTER_SCOPED_KINDS.contains(&kind.as_str()) => String::new(),
+        None => default_namespace.to_string(),
+    };
+
+    object.remove("status");
+    if let Some(Value::Mapping(metadata)) = object.get_mut("metadata") {
+        for key in SERVER_MANAGED_METADATA {
+            metadata.remove(key);
+        }
+        if !namespace.is_empty() {
+            metadata.insert("namespace".to_string(), Value::from(namespace.clone()));
+        }
+    }
+    sort_keys(&mut object);
+
+    let file_name = format!(
+        "{}.{kind}.{namespace}.{name}",
+        api_version.replace('/', ".")
+    );
+    let mut body = serde_yml::to_string(&object)?;
+    if !body.ends_with('\n') {
+        body.push('\n');
+    }
+    Ok((file_name, body))
+}
+
+/// Sort mapping keys recursively as kubectl does when it writes the objects to compare.
+fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Mapping(mapping) => {
+            mapping.sort_keys();
+            mapping.values_mut().for_each(sort_keys);
+        }
+        Value::Sequence(sequence) => sequence.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{DiffParser, Parsable};
+    use crate::resource::Action;
+
+    const SERVICE: &str = "apiVersion: v1
+kind: Service
+metadata:
+  name: app
+  namespace: test
+spec:
+  ports:
+  - port: 80
+";
+
+    #[test]
+    fn test_diff_of_identical_manifests_is_empty() {
+        let actual = diff(SERVICE, SERVICE, "default", false).unwrap();
+        assert_eq!(actual, "");
+    }
+
+    #[test]
+    fn test_diff_ignores_key_order_and_server_managed_fields() {
+        let live = "kind: Service
+apiVersion: v1
+metadata:
+  namespace: test
+  name: app
+  uid: 1234
+  resourceVersion: '42'
+  managedFields:
+  - manager: kubectl
+spec:
+  ports:
+  - port: 80
+status:
+  loadBalancer: {}
+";
+        let actual = diff(live, SERVICE, "default", false).unwrap();
+        assert_eq!(actual, "");
+    }
+
+    #[test]
+    fn test_diff_puts_namespaced_objects_without_namespace_in_default_namespace() {
+        let live = SERVICE.replace("namespace: test", "namespace: default");
+        let desired = SERVICE.replace("  namespace: test\n", "");
+        let actual = diff(&live, &desired, "default", false).unwrap();
+        assert_eq!(actual, "");
+
+        let actual = diff(&live, &desired, "test", false).unwrap();
+        assert!(actual.contains("/tmp/LIVE/v1.Service.test.app"));
+        assert!(actual.contains("/tmp/LIVE/v1.Service.default.app"));
+    }
+
+    #[test]
+    fn test_diff_does_not_put_cluster_scoped_objects_in_namespace() {
+        let live =
+            "apiVersion: rbac.authorization.k8s.io/v1\nkind: ClusterRole\nmetadata:\n  name: app\n";
+        let desired = format!("{live}rules: []\n");
+        let actual = diff(live, &desired, "default", false).unwrap();
+        assert!(actual.contains("/tmp/LIVE/rbac.authorization.k8s.io.v1.ClusterRole..app"));
+        assert!(!actual.contains("namespace:"));
+    }
+
+    #[test]
+    fn test_diff_configured_object() {
+        let desired = SERVICE.replace("port: 80", "port: 8080");
+        let actual = diff(SERVICE, &desired, "default", false).unwrap();
+        assert!(actual.starts_with(
+            "diff -u -N /tmp/LIVE/v1.Service.test.app /tmp/MERGED/v1.Service.test.app\n--- /tmp/LIVE/v1.Service.test.app\n+++ /tmp/MERGED/v1.Service.test.app\n@@ "
+        ));
+        assert!(actual.ends_with("\n-    - port: 80\n+    - port: 8080\n"));
+    }
+
+    #[test]
+    fn test_diff_with_full_context_covers_whole_document() {
+        let desired = SERVICE.replace("port: 80", "port: 8080");
+        let actual = diff(SERVICE, &desired, "default", true).unwrap();
+        assert!(actual.contains("\n@@ -1,8 +1,8 @@\n apiVersion: v1\n"));
+    }
+
+    #[test]
+    fn test_diff_flattens_list_and_splits_documents() {
+        let live = "apiVersion: v1
+kind: List
+items:
+- apiVersion: v1
+  kind: ConfigMap
+  metadata:
+    name: pruned
+    namespace: test
+";
+        let desired = format!(
+            "---\n{SERVICE}---\napiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: app\n  namespace: test\n"
+        );
+        let actual = diff(live, &desired, "default", false).unwrap();
+        assert!(actual.contains("/tmp/LIVE/apps.v1.Deployment.test.app"));
+        assert!(actual.contains("/tmp/LIVE/v1.ConfigMap.test.pruned"));
+        assert!(actual.contains("/tmp/LIVE/v1.Service.test.app"));
+    }
+
+    #[test]
+    fn test_diff_result_is_parsable_by_diff_parser() {
+        let live = format!(
+            "{SERVICE}---\napiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: pruned\n  namespace: test\n"
+        );
+        let desired = format!(
+            "{}---\napiVersion: v1\nkind: Namespace\nmetadata:\n  name: test\n",
+            SERVICE.replace("port: 80", "port: 8080")
+        );
+        let body = diff(&live, &desired, "default", false).unwrap();
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual: Vec<(String, Action)> = parser
+            .parse(&body)
+            .unwrap()
+            .resources
+            .iter()
+            .map(|r| (r.id(), r.action))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                ("v1.ConfigMap.test.pruned".to_string(), Action::Pruned),
+                ("v1.Namespace..test".to_string(), Action::Created),
+                ("v1.Service.test.app".to_string(), Action::Configured),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_with_object_without_name_returns_error() {
+        let actual = diff(
+            "apiVersion: v1\nkind: Service\nmetadata: {}\n",
+            SERVICE,
+            "default",
+            false,
+        );
+        assert!(actual.is_err());
+    }
+
+    #[test]
+    fn test_diff_with_duplicated_object_returns_error() {
+        let live = format!("{SERVICE}---\n{SERVICE}");
+        assert!(diff(&live, SERVICE, "default", false).is_err());
+    }
+
+    #[test]
+    fn test_diff_files_reads_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let live = temp_dir.path().join("live.yaml");
+        let desired = temp_dir.path().join("desired.yaml");
+        fs::write(&live, SERVICE).unwrap();
+        fs::write(&desired, SERVICE.replace("port: 80", "port: 8080")).unwrap();
+        let actual = diff_files(&live, &desired, "default", false).unwrap();
+        assert!(actual.contains("+    - port: 8080"));
+    }
+
+    #[test]
+    fn test_diff_files_with_missing_file_returns_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let missing = temp_dir.path().join("missing.yaml");
+        assert!(diff_files(&missing, &missing, "default", false).is_err());
+    }
+}
@@ -1,19 +1,193 @@
-use anyhow::Result;
-use log::debug;
+use crate::redaction::Redactor;
+use crate::resource::{Action, Hunk, LineStats, ResourceChange, ResourceSelector};
+use crate::semantic;
+
+use anyhow::{Context, Result, anyhow, bail};
+use itertools::Itertools;
+use log::{debug, warn};
 use regex::Regex;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::iter::{Enumerate, Peekable};
+use std::str::Lines;
+use std::sync::LazyLock;
+use strum_macros::EnumString;
+
+// matches the key of a YAML mapping entry like "name: app", "labels:" or "\"helm.sh/chart\": x"
+static YAML_KEY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?P<key>"[^"]*"|'[^']*'|[^\s"'#{\[][^:#]*?):(?:\s|$)"#).unwrap()
+});
 
 pub trait Parsable {
     fn parse(&self, body: &str) -> Result<ParseResult>;
 }
 
+/// Format of the diff given to ksnotify, which depends on `KUBECTL_EXTERNAL_DIFF`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, EnumString, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffDialect {
+    /// Detect the dialect from the input.
+    #[default]
+    #[strum(serialize = "auto")]
+    Auto,
+
+    /// `diff -u`, `diff -u -N` (kubectl default) or `diff -ruN` output.
+    #[strum(serialize = "unified")]
+    Unified,
+
+    /// `git diff --no-index` style output starting with `diff --git`.
+    #[strum(serialize = "git")]
+    Git,
+
+    /// Either of the above colored with ANSI escape codes, e.g. `colordiff` or `diff --color=always`.
+    #[strum(serialize = "ansi")]
+    Ansi,
+}
+
+/// Labels and annotations that a controller adds or updates on every rollout.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, EnumString, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SuppressPreset {
+    /// `helm.sh/chart`, `app.kubernetes.io/managed-by` and `meta.helm.sh/release-*`.
+    #[strum(serialize = "helm")]
+    Helm,
+
+    /// `kustomize.toolkit.fluxcd.io/*` and `helm.toolkit.fluxcd.io/*` set by Flux.
+    #[strum(serialize = "flux")]
+    Flux,
+
+    /// Build metadata like `config.kubernetes.io/origin` set by `kustomize build`.
+    #[strum(serialize = "kustomize")]
+    Kustomize,
+
+    /// Sidecar injection status set by Istio.
+    #[strum(serialize = "istio")]
+    Istio,
+}
+
+impl SuppressPreset {
+    /// Regexes of the label and annotation keys the preset suppresses.
+    const fn keys(self) -> &'static [&'static str] {
+        match self {
+            Self::Helm => &[
+                r"helm\.sh/chart",
+                r"app\.kubernetes\.io/managed-by",
+                r"meta\.helm\.sh/release-[\w-]+",
+            ],
+            Self::Flux => &[
+                r"kustomize\.toolkit\.fluxcd\.io/[\w.-]+",
+                r"helm\.toolkit\.fluxcd\.io/[\w.-]+",
+            ],
+            Self::Kustomize => &[
+                r"config\.kubernetes\.io/origin",
+                r"internal\.config\.kubernetes\.io/[\w.-]+",
+                r"kustomize\.config\.k8s\.io/[\w.-]+",
+            ],
+            Self::Istio => &[
+                r"sidecar\.istio\.io/status",
+                r"kubectl\.kubernetes\.io/default-container",
+                r"kubectl\.kubernetes\.io/default-logs-container",
+            ],
+        }
+    }
+}
+
+/// User-defined rule to suppress diff lines, configured by `suppress_rules` in the config file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct SuppressRule {
+    pub name: String,
+    /// Regex matched against the content of added and removed lines.
+    #[serde(default)]
+    pub line: Option<String>,
+    /// YAML path like `metadata.annotations."kubectl.kubernetes.io/last-applied-configuration"`.
+    /// Added and removed lines under the path are suppressed. List items are not part of the path.
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Resources the rule applies to. All resources by default.
+    #[serde(default)]
+    pub selector: ResourceSelector,
+    /// Whether a resource is dropped when all of its diff is suppressed.
+    #[serde(default = "SuppressRule::default_drop")]
+    pub drop: bool,
+}
+
+impl SuppressRule {
+    const fn default_drop() -> bool {
+        true
+    }
+}
+
+/// Lines a `SuppressRule` removes.
+#[derive(Debug)]
+enum LineMatcher {
+    Line(Regex),
+    Path(Vec<String>),
+}
+
+/// Compiled `SuppressRule`.
+#[derive(Debug)]
+struct Suppression {
+    matcher: LineMatcher,
+    selector: ResourceSelector,
+    drop: bool,
+}
+
+#[derive(Debug)]
 pub struct ParseResult {
-    pub kind_result: HashMap<String, String>,
+    /// Changed resources sorted by their identifier.
+    pub resources: Vec<ResourceChange>,
+}
+
+/// Diff of a single file (i.e. a single resource) in the `kubectl diff` output.
+#[derive(Debug, PartialEq, Eq)]
+struct FileDiff {
+    name: String,
+    /// The old side is `/dev/null`.
+    old_missing: bool,
+    /// The new side is `/dev/null`.
+    new_missing: bool,
+    hunks: Vec<Hunk>,
+}
+
+/// Line iterator that remembers line numbers for error messages.
+struct DiffLines<'a> {
+    lines: Peekable<Enumerate<Lines<'a>>>,
+}
+
+impl<'a> DiffLines<'a> {
+    fn new(body: &'a str) -> Self {
+        Self {
+            lines: body.lines().enumerate().peekable(),
+        }
+    }
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        self.lines.next().map(|(i, line)| (i + 1, line))
+    }
+
+    fn peek(&mut self) -> Option<&'a str> {
+        self.lines.peek().map(|(_, line)| *line)
+    }
+
+    /// Line number of the next line.
+    fn line_number(&mut self) -> usize {
+        self.lines.peek().map_or(0, |(i, _)| i + 1)
+    }
+
+    /// Take the next line, which must start with `prefix`.
+    fn expect(&mut self, prefix: &str) -> Result<(usize, &'a str)> {
+        match self.next() {
+            Some((n, line)) if line.starts_with(prefix) => Ok((n, line)),
+            Some((n, line)) => bail!("line {n}: expected \"{prefix}\" header but found {line:?}"),
+            None => bail!("unexpected end of diff: expected \"{prefix}\" header"),
+        }
+    }
 }
 
 pub struct DiffParser {
-    kind: Regex,
-    header: Regex,
+    dialect: DiffDialect,
+    ansi_escape: Regex,
     diff: Regex,
     skaffold: Regex,
     argocd: Regex,
@@ -21,6 +195,10 @@ pub struct DiffParser {
     suppress_argocd: bool,
     generation: Regex,
     ignore_tag_images: Vec<String>,
+    presets: Option<Regex>,
+    suppressions: Vec<Suppression>,
+    redactor: Redactor,
+    semantic_diff: bool,
 }
 
 impl DiffParser {
@@ -29,15 +207,14 @@ impl DiffParser {
         suppress_argocd: bool,
         ignore_tag_images: Vec<String>,
     ) -> Result<Self> {
-        let kind = Regex::new(r"(?m)^diff -u -N\s.*/(?P<kind>[^/\s]+)\s.*/([^/\s]+)$")?; // matches line like "diff -u -N /var/folders/fl/blahblah/[apiVersion].[kind].[namespace].[name]  /var/folders/fl/blahblah/[apiVersion].[kind].[namespace].[name]"
-        let header = Regex::new(r"(?m)^((diff -u -N)|(\-\-\-)|(\+\+\+)).*$")?; // matches diff header that starts with "diff -u -N" or "---" or "+++"
+        let ansi_escape = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]")?; // matches ANSI CSI sequence like "\x1b[0;31m"
         let diff = Regex::new(r"(?m)^[\-\+].*$")?;
         let skaffold = Regex::new(r"(?m)^(.*labels:.*\r?\n?)?.*skaffold.dev/run-id.*\r?\n?")?;
         let generation = Regex::new(r"(?m)^.*generation: \d+.*\r?\n?")?;
         let argocd = Regex::new(r"(?m)^.*argocd.argoproj.io/tracking-id:.*\r?\n?")?;
         Ok(Self {
-            kind,
-            header,
+            dialect: DiffDialect::default(),
+            ansi_escape,
             diff,
             skaffold,
             argocd,
@@ -45,39 +222,359 @@ impl DiffParser {
             suppress_argocd,
             generation,
             ignore_tag_images,
+            presets: None,
+            suppressions: Vec::new(),
+            redactor: Redactor::default(),
+            semantic_diff: false,
         })
     }
 
-    fn parse_kinds(&self, diff: &str) -> Vec<String> {
-        self.kind
-            .captures_iter(diff)
-            .filter_map(|cap| Some(cap.name("kind")?.as_str().to_string()))
-            .collect()
+    pub const fn with_dialect(mut self, dialect: DiffDialect) -> Self {
+        self.dialect = dialect;
+        self
     }
 
-    fn parse_diff(&self, diff: &str) -> Vec<String> {
-        self.header
-            .split(diff)
-            .filter(|x| !x.trim().is_empty())
-            .map(|x| x.trim().to_string())
-            .collect()
+    /// Suppress labels and annotations of the given presets.
+    pub fn with_suppress_presets(mut self, presets: &[SuppressPreset]) -> Result<Self> {
+        let keys = presets.iter().flat_map(|p| p.keys()).join("|");
+        if !keys.is_empty() {
+            // matches the key line like "    helm.sh/chart: app-1.0.0" in the diff line content
+            self.presets = Some(Regex::new(&format!(
+                r#"^\s*["']?(?:{keys})["']?:(?:\s|$)"#
+            ))?);
+        }
+        Ok(self)
+    }
+
+    /// Suppress diff lines by user-defined rules in addition to the built-in suppressions.
+    pub fn with_suppress_rules(mut self, rules: &[SuppressRule]) -> Result<Self> {
+        for rule in rules {
+            let matcher = match (&rule.line, &rule.path) {
+                (Some(line), None) => LineMatcher::Line(
+                    Regex::new(line)
+                        .with_context(|| format!("invalid line regex in rule {}", rule.name))?,
+                ),
+                (None, Some(path)) => LineMatcher::Path(
+                    Self::parse_yaml_path(path)
+                        .with_context(|| format!("invalid path in rule {}", rule.name))?,
+                ),
+                _ => bail!("rule {} must have either line or path", rule.name),
+            };
+            self.suppressions.push(Suppression {
+                matcher,
+                selector: rule.selector.clone(),
+                drop: rule.drop,
+            });
+        }
+        Ok(self)
+    }
+
+    /// Mask values of keys matching `keys` (case-insensitive regexes) in addition to Secret data.
+    pub fn with_redact_keys(mut self, keys: &[String]) -> Result<Self> {
+        self.redactor = Redactor::new(keys).context("invalid redact key pattern")?;
+        Ok(self)
+    }
+
+    /// Compute path-based changes of configured resources in addition to the line diff.
+    pub const fn with_semantic_diff(mut self, semantic_diff: bool) -> Self {
+        self.semantic_diff = semantic_diff;
+        self
+    }
+
+    /// Strip ANSI escape codes if the input is colored.
+    fn normalize<'a>(&self, body: &'a str) -> Cow<'a, str> {
+        match self.dialect {
+            DiffDialect::Ansi => self.ansi_escape.replace_all(body, ""),
+            DiffDialect::Auto if body.contains('\x1b') => self.ansi_escape.replace_all(body, ""),
+            _ => Cow::Borrowed(body),
+        }
+    }
+
+    /// Split the whole `kubectl diff` output into per-file diffs.
+    /// Each file starts with an optional `diff -u -N <live> <merged>` (or `diff --git a/<live> b/<merged>`) line followed by `---` and `+++` headers and one or more hunks.
+    fn parse_files(&self, body: &str) -> Result<Vec<FileDiff>> {
+        let mut lines = DiffLines::new(body);
+        let mut files = Vec::new();
+        while let Some(line) = lines.peek() {
+            if line.trim().is_empty() {
+                lines.next();
+                continue;
+            }
+            if line.starts_with("Only in ") {
+                // printed by `diff -r` without `-N` for a resource missing on one side
+                warn!("skip {line:?}: use `diff -N` to show created and pruned resources");
+                lines.next();
+                continue;
+            }
+            files.push(self.parse_file(&mut lines)?);
+        }
+        Ok(files)
+    }
+
+    fn parse_file(&self, lines: &mut DiffLines) -> Result<FileDiff> {
+        // kubectl names the temporary files "[group.]version.kind.namespace.name", so the
+        // file name on the "diff" line identifies the resource. Fall back to the "+++" path.
+        let name = match lines.peek() {
+            Some(l) if l.starts_with("diff --git ") && self.dialect != DiffDialect::Unified => {
+                let (_, line) = lines.expect("diff --git ")?;
+                // extended headers such as "index 123..456" or "new file mode 100644"
+                while lines
+                    .peek()
+                    .is_some_and(|l| !l.starts_with("--- ") && !l.starts_with("diff "))
+                {
+                    lines.next();
+                }
+                line.split_whitespace().last().map(Self::file_name)
+            }
+            Some(l) if l.starts_with("diff ") && self.dialect != DiffDialect::Git => {
+                let (_, line) = lines.expect("diff ")?;
+                line.split_whitespace().last().map(Self::file_name)
+            }
+            Some(l) if self.dialect == DiffDialect::Git => {
+                let n = lines.line_number();
+                bail!("line {n}: expected \"diff --git\" header but found {l:?}")
+            }
+            _ => None,
+        };
+        let (_, old) = lines.expect("--- ")?;
+        let (n, new) = lines.expect("+++ ")?;
+        let name = name
+            .or_else(|| Self::header_path(new).map(Self::file_name))
+            .or_else(|| Self::header_path(old).map(Self::file_name))
+            .with_context(|| format!("line {n}: cannot find file name in diff header"))?;
+
+        let mut hunks = vec![self.parse_hunk(lines)?];
+        while lines.peek().is_some_and(|l| l.starts_with("@@")) {
+            hunks.push(self.parse_hunk(lines)?);
+        }
+        Ok(FileDiff {
+            name,
+            old_missing: Self::header_path(old).is_none(),
+            new_missing: Self::header_path(new).is_none(),
+            hunks,
+        })
+    }
+
+    fn parse_hunk(&self, lines: &mut DiffLines) -> Result<Hunk> {
+        let (n, header) = lines.expect("@@")?;
+        let mut hunk = Hunk::parse_header(header)
+            .ok_or_else(|| anyhow!("line {n}: malformed hunk header {header:?}"))?;
+
+        // read exactly as many lines as the header announces, so that a body line which
+        // happens to look like a header (e.g. a removed "-- " line) is never mistaken for one
+        let (mut old, mut new) = (hunk.old_lines, hunk.new_lines);
+        while old > 0 || new > 0 {
+            let (n, line) = lines
+                .next()
+                .ok_or_else(|| anyhow!("unexpected end of diff in hunk {header:?}"))?;
+            let (old_used, new_used) = match line.chars().next() {
+                // some tools strip the trailing space of empty context lines
+                Some(' ') | None => (1, 1),
+                Some('-') => (1, 0),
+                Some('+') => (0, 1),
+                Some('\\') => (0, 0),
+                Some(_) => bail!("line {n}: unexpected line in hunk {header:?}: {line:?}"),
+            };
+            if old < old_used || new < new_used {
+                bail!("line {n}: hunk {header:?} has more lines than its header announces");
+            }
+            old -= old_used;
+            new -= new_used;
+            hunk.lines.push(line.to_string());
+        }
+        // "\ No newline at end of file" follows the last line of a hunk
+        while lines.peek().is_some_and(|l| l.starts_with('\\')) {
+            if let Some((_, line)) = lines.next() {
+                hunk.lines.push(line.to_string());
+            }
+        }
+        Ok(hunk)
+    }
+
+    /// Merge a resource that is pruned and created in the same diff into a single replaced resource.
+    /// `result` must be sorted by identifier.
+    fn merge_replaced(result: Vec<ResourceChange>) -> Vec<ResourceChange> {
+        let mut merged: Vec<ResourceChange> = Vec::with_capacity(result.len());
+        for change in result {
+            match merged.pop() {
+                Some(prev) if prev.id() == change.id() => {
+                    merged.extend(Self::merge_pair(prev, change));
+                }
+                Some(prev) => merged.extend([prev, change]),
+                None => merged.push(change),
+            }
+        }
+        merged
+    }
+
+    fn merge_pair(prev: ResourceChange, next: ResourceChange) -> Vec<ResourceChange> {
+        let (pruned, created) = match (prev.action, next.action) {
+            (Action::Pruned, Action::Created) => (prev, next),
+            (Action::Created, Action::Pruned) => (next, prev),
+            _ => return vec![prev, next],
+        };
+        let stats = LineStats {
+            added: pruned.stats.added + created.stats.added,
+            removed: pruned.stats.removed + created.stats.removed,
+        };
+        vec![ResourceChange {
+            action: Action::Replaced,
+            hunks: pruned.hunks.into_iter().chain(created.hunks).collect(),
+            stats,
+            ..created
+        }]
+    }
+
+    /// Path of a `---`/`+++` header, without the trailing timestamp. `None` for `/dev/null`.
+    fn header_path(line: &str) -> Option<&str> {
+        let path = line.get(4..)?.split('\t').next()?.trim();
+        (!path.is_empty() && path != "/dev/null").then_some(path)
+    }
+
+    fn file_name(path: &str) -> String {
+        path.rsplit('/').next().unwrap_or(path).to_string()
     }
 
     fn suppress_by(
         &self,
-        result: HashMap<String, String>,
+        result: Vec<ResourceChange>,
         remove_fn: impl Fn(&str) -> String,
-    ) -> HashMap<String, String> {
+    ) -> Vec<ResourceChange> {
+        self.suppress_matching(result, |_| true, remove_fn, true)
+    }
+
+    /// Apply `remove_fn` to the resources `selector_fn` matches. A matched resource without
+    /// remaining diff is dropped if `drop` is true.
+    fn suppress_matching(
+        &self,
+        result: Vec<ResourceChange>,
+        selector_fn: impl Fn(&ResourceChange) -> bool,
+        remove_fn: impl Fn(&str) -> String,
+        drop: bool,
+    ) -> Vec<ResourceChange> {
         result
             .into_iter()
-            .map(|(kind, diff)| {
-                let cleaned = remove_fn(&diff);
-                (kind, cleaned)
+            .map(|change| {
+                if selector_fn(&change) {
+                    change.map_diff(&remove_fn)
+                } else {
+                    change
+                }
+            })
+            .filter(|change| {
+                !drop || !selector_fn(change) || self.is_there_any_diff(&change.diff())
             })
-            .filter(|(_kind, diff)| self.is_there_any_diff(diff))
             .collect()
     }
 
+    fn suppress_by_rule(
+        &self,
+        result: Vec<ResourceChange>,
+        suppression: &Suppression,
+    ) -> Vec<ResourceChange> {
+        let selector_fn = |change: &ResourceChange| suppression.selector.matches(change);
+        match &suppression.matcher {
+            LineMatcher::Line(re) => self.suppress_matching(
+                result,
+                selector_fn,
+                |d| Self::remove_matching_lines(d, re),
+                suppression.drop,
+            ),
+            LineMatcher::Path(path) => self.suppress_matching(
+                result,
+                selector_fn,
+                |d| Self::remove_lines_under_path(d, path),
+                suppression.drop,
+            ),
+        }
+    }
+
+    /// Remove added and removed lines whose content matches `re`.
+    fn remove_matching_lines(diff: &str, re: &Regex) -> String {
+        diff.lines()
+            .filter(|line| !(line.starts_with(['+', '-']) && re.is_match(&line[1..])))
+            .join("\n")
+    }
+
+    /// Remove added and removed lines under the YAML `path`. The path of each line is inferred
+    /// from the keys and indentation of the preceding lines in the same hunk. As a hunk may start
+    /// in the middle of a document, a line also matches if its path lacks leading segments of `path`.
+    fn remove_lines_under_path(diff: &str, path: &[String]) -> String {
+        // keys of the enclosing mappings with their indentation
+        let mut stack: Vec<(usize, String)> = Vec::new();
+        diff.lines()
+            .filter(|line| {
+                if line.starts_with("@@") {
+                    stack.clear();
+                    return true;
+                }
+                let Some(content) = line.strip_prefix(['+', '-', ' ']) else {
+                    return true;
+                };
+                if content.trim().is_empty() {
+                    return true;
+                }
+                let indent = content.len() - content.trim_start().len();
+                let mut rest = content.trim_start();
+                let mut list_depth = 0;
+                while let Some(item) = rest.strip_prefix("- ") {
+                    rest = item.trim_start();
+                    list_depth += 1;
+                }
+                // a list item stays under the key it is aligned with
+                let scope_indent = if list_depth > 0 { indent + 1 } else { indent };
+                while stack.last().is_some_and(|(i, _)| *i >= scope_indent) {
+                    stack.pop();
+                }
+                if let Some(caps) = YAML_KEY.captures(rest) {
+                    let key = caps["key"].trim_matches(['"', '\'']).to_string();
+                    stack.push((content.len() - rest.len(), key));
+                }
+                let line_path: Vec<&String> = stack.iter().map(|(_, k)| k).collect();
+                let under_path = (0..path.len()).any(|skip| {
+                    let expected = &path[skip..];
+                    line_path.len() >= expected.len()
+                        && line_path.iter().zip(expected).all(|(a, b)| *a == b)
+                });
+                line.starts_with(' ') || !under_path
+            })
+            .join("\n")
+    }
+
+    /// Parse a dot-separated YAML path. Segments containing dots are quoted as `"a.b"` or `["a.b"]`.
+    fn parse_yaml_path(path: &str) -> Result<Vec<String>> {
+        let mut segments = Vec::new();
+        let mut rest = path;
+        while !rest.is_empty() {
+            rest = rest.strip_prefix('.').unwrap_or(rest);
+            let quoted = rest
+                .strip_prefix("[\"")
+                .map(|r| (r, "\"]"))
+                .or_else(|| rest.strip_prefix('"').map(|r| (r, "\"")));
+            let (segment, remaining) = match quoted {
+                Some((r, close)) => {
+                    let end = r
+                        .find(close)
+                        .ok_or_else(|| anyhow!("unterminated quote in path: {path}"))?;
+                    (&r[..end], &r[end + close.len()..])
+                }
+                None => {
+                    let end = rest.find(['.', '[', '"']).unwrap_or(rest.len());
+                    (&rest[..end], &rest[end..])
+                }
+            };
+            if segment.is_empty() {
+                bail!("empty segment in path: {path}");
+            }
+            segments.push(segment.to_string());
+            rest = remaining;
+        }
+        if segments.is_empty() {
+            bail!("empty path");
+        }
+        Ok(segments)
+    }
+
     fn remove_skaffold_labels(&self, diff: &str) -> String {
         self.skaffold.replace_all(diff, "").to_string()
     }
@@ -86,6 +583,32 @@ impl DiffParser {
         self.argocd.replace_all(diff, "").to_string()
     }
 
+    /// Remove the label and annotation lines of the presets, and the lines of their block values.
+    fn remove_preset_fields(&self, diff: &str) -> String {
+        let Some(presets) = &self.presets else {
+            return diff.to_string();
+        };
+        // indentation of the removed key while its block value is being removed
+        let mut block_indent: Option<usize> = None;
+        diff.split_inclusive('\n')
+            .filter(|line| {
+                let content = line.get(1..).unwrap_or_default();
+                let indent = content.len() - content.trim_start().len();
+                if let Some(i) = block_indent {
+                    if indent > i && !content.trim().is_empty() && !line.starts_with("@@") {
+                        return false;
+                    }
+                    block_indent = None;
+                }
+                if presets.is_match(content) {
+                    block_indent = Some(indent);
+                    return false;
+                }
+                true
+            })
+            .collect()
+    }
+
     fn remove_generation_fields(&self, diff: &str) -> String {
         self.generation.replace_all(diff, "").to_string()
     }
@@ -106,15 +629,35 @@ impl DiffParser {
 
 impl Parsable for DiffParser {
     fn parse(&self, diff: &str) -> Result<ParseResult> {
-        let kinds = self.parse_kinds(diff);
-        debug!("kinds: {kinds:?}");
-        let chunked_diff = self.parse_diff(diff);
-        debug!("chunked diff: {chunked_diff:?}");
+        let files = self.parse_files(&self.normalize(diff))?;
+        // only the names, as the hunks are not redacted yet
+        debug!(
+            "files: {:?}",
+            files.iter().map(|f| f.name.as_str()).collect::<Vec<_>>()
+        );
 
-        let mut result: HashMap<_, _> = kinds
-            .iter()
-            .zip(chunked_diff.iter())
-            .map(|(k, v)| (k.to_string(), v.to_string()))
+        let mut result = files
+            .into_iter()
+            .map(|f| {
+                let action = Action::classify(f.old_missing, f.new_missing, &f.hunks);
+                ResourceChange::from_hunks(&f.name, action, f.hunks)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        result.sort_by_key(ResourceChange::id);
+        result = Self::merge_replaced(result);
+        // whether the hunks cover the whole documents is known only before they are rewritten
+        let whole_documents: HashSet<String> = if self.semantic_diff {
+            result
+                .iter()
+                .filter(|change| semantic::is_whole_document(&change.hunks))
+                .map(ResourceChange::id)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        result = result
+            .into_iter()
+            .map(|change| self.redactor.redact(change))
             .collect();
 
         result = self.suppress_by(result, |d| self.remove_generation_fields(d));
@@ -128,16 +671,41 @@ impl Parsable for DiffParser {
         for image_name in &self.ignore_tag_images {
             result = self.suppress_by(result, |d| self.remove_image_tags(d, image_name));
         }
-        debug!("result: {result:?}");
+        if self.presets.is_some() {
+            result = self.suppress_by(result, |d| self.remove_preset_fields(d));
+        }
+        for suppression in &self.suppressions {
+            result = self.suppress_by_rule(result, suppression);
+        }
+        if self.semantic_diff {
+            for change in &mut result {
+                if change.action == Action::Configured && whole_documents.contains(&change.id()) {
+                    change.changes = semantic::changes(&change.hunks);
+                }
+            }
+        }
+        debug!(
+            "result: {:?}",
+            result
+                .iter()
+                .map(|r| format!(
+                    "{} ({}, +{}/-{})",
+                    r.id(),
+                    r.action,
+                    r.stats.added,
+                    r.stats.removed
+                ))
+                .collect::<Vec<_>>()
+        );
 
-        Ok(ParseResult {
-            kind_result: result,
-        })
+        Ok(ParseResult { resources: result })
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use super::*;
 
     #[test]
@@ -145,59 +713,500 @@ mod tests {
         let diff = "diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app1 /var/folders/fl/blahblah/v1.Service.test.test-app1
 --- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
 +++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
 - ABCDE
 + FGHIJ
 diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app2 /var/folders/fl/blahblah/v1.Service.test.test-app2
 --- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
 +++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
 - 12345
 + 67890";
         let parser = self::DiffParser::new(false, false, Vec::new()).unwrap();
         let actual = parser.parse(diff).unwrap();
-        assert_eq!(actual.kind_result.len(), 2);
+        assert_eq!(actual.resources.len(), 2);
 
         let keys = ["v1.Service.test.test-app1", "v1.Service.test.test-app2"];
-        let values = vec!["- ABCDE\n+ FGHIJ", "- 12345\n+ 67890"];
-        for (k, v) in keys.iter().zip(values) {
-            assert_eq!(actual.kind_result[&k.to_string()], v.to_string())
+        let values = vec![
+            "@@ -1 +1 @@\n- ABCDE\n+ FGHIJ",
+            "@@ -1 +1 @@\n- 12345\n+ 67890",
+        ];
+        for ((k, v), change) in keys.iter().zip(values).zip(&actual.resources) {
+            assert_eq!(change.id(), k.to_string());
+            assert_eq!(change.diff(), v.to_string());
         }
     }
 
     #[test]
-    fn test_parse_kinds_correctly_extracts_kind() {
+    fn test_parse_files_correctly_extracts_file_names() {
         let diff = "diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app1 /var/folders/fl/blahblah/v1.Service.test.test-app1
 --- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
 +++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-ABCDE
-FGHIJ
+@@ -1,2 +1,2 @@
+ ABCDE
+ FGHIJ
 diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app2 /var/folders/fl/blahblah/v1.Service.test.test-app2
 --- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
 +++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-12345
-67890";
+@@ -1,2 +1,2 @@
+ 12345
+ 67890";
 
         let parser = self::DiffParser::new(false, false, Vec::new()).unwrap();
-        let actual = parser.parse_kinds(diff);
+        let actual: Vec<String> = parser
+            .parse_files(diff)
+            .unwrap()
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
         let expected = ["v1.Service.test.test-app1", "v1.Service.test.test-app2"];
         assert_eq!(&actual[..], &expected[..]);
     }
 
     #[test]
-    fn test_parse_diff_correctly_extracts_list_of_diff_body() {
-        let diff = "diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app /var/folders/fl/blahblah/v1.Service.test.test-app
---- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-+++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-ABCDE
-FGHIJ
-diff -u -N /var/folders/fl/blahblah/v1.Service.test.test-app2 /var/folders/fl/blahblah/v1.Service.test.test-app2
---- /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-+++ /var/folders/fl/blahblah/v1.Service.test.test-app	2022-02-22 22:00:00.000000000 +0900
-12345
-67890";
-        let parser = self::DiffParser::new(false, false, Vec::new()).unwrap();
-        let actual = parser.parse_diff(diff);
-        let expected = ["ABCDE\nFGHIJ", "12345\n67890"];
-        assert_eq!(&actual[..], &expected[..]);
+    fn test_parse_files_falls_back_to_new_file_path_without_diff_line() {
+        let diff = "--- /tmp/LIVE-1/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
+-a
++b";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].name, "v1.Service.test.app");
+    }
+
+    #[test]
+    fn test_parse_files_extracts_hunks_with_ranges() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1,2 +1,2 @@
+ metadata:
+-  name: a
++  name: b
+@@ -10,2 +10,3 @@ spec:
+   ports:
++  - port: 80
+   type: ClusterIP
+";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff).unwrap();
+        assert_eq!(actual.len(), 1);
+        let hunks = &actual[0].hunks;
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(
+            (
+                hunks[1].old_start,
+                hunks[1].old_lines,
+                hunks[1].new_start,
+                hunks[1].new_lines
+            ),
+            (10, 2, 10, 3)
+        );
+        assert_eq!(
+            hunks[1].lines,
+            vec!["   ports:", "+  - port: 80", "   type: ClusterIP"]
+        );
+    }
+
+    #[test]
+    fn test_parse_files_does_not_mistake_body_lines_for_headers() {
+        // a removed "-- a" line and an added "++ b" line look like "---"/"+++" headers
+        let diff =
+            "diff -u -N /tmp/LIVE-1/v1.ConfigMap.test.app /tmp/MERGED-2/v1.ConfigMap.test.app
+--- /tmp/LIVE-1/v1.ConfigMap.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.ConfigMap.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1,2 +1,2 @@
+--- a
++++ b
+ diff -u -N in a context line";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert_eq!(
+            actual[0].hunks[0].lines,
+            vec!["--- a", "+++ b", " diff -u -N in a context line"]
+        );
+    }
+
+    #[test]
+    fn test_parse_files_keeps_no_newline_marker() {
+        let diff = "--- /tmp/LIVE-1/v1.ConfigMap.test.app
++++ /tmp/MERGED-2/v1.ConfigMap.test.app
+@@ -1 +1 @@
+-a
+\\ No newline at end of file
++b
+\\ No newline at end of file";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff).unwrap();
+        assert_eq!(actual[0].hunks[0].lines.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_files_without_hunk_header_returns_error() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+- ABCDE
++ FGHIJ";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff);
+        assert!(actual.unwrap_err().to_string().contains("line 4"));
+    }
+
+    #[test]
+    fn test_parse_files_with_missing_file_header_returns_error() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+@@ -1 +1 @@
+-a
++b";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        assert!(parser.parse_files(diff).is_err());
+    }
+
+    #[test]
+    fn test_parse_files_with_truncated_hunk_returns_error() {
+        let diff = "--- /tmp/LIVE-1/v1.Service.test.app
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -1,3 +1,3 @@
+ a
+-b
++c";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse_files(diff);
+        assert!(
+            actual
+                .unwrap_err()
+                .to_string()
+                .contains("unexpected end of diff")
+        );
+    }
+
+    #[test]
+    fn test_parse_files_with_extra_hunk_line_returns_error() {
+        let diff = "--- /tmp/LIVE-1/v1.Service.test.app
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -1 +1 @@
+-b
++c
++d";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        assert!(parser.parse_files(diff).is_err());
+    }
+
+    #[test]
+    fn test_parse_classifies_actions_from_headers() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.ConfigMap.test.created /tmp/MERGED-2/v1.ConfigMap.test.created
+--- /tmp/LIVE-1/v1.ConfigMap.test.created	1970-01-01 09:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.ConfigMap.test.created	2022-02-22 22:00:00.000000000 +0900
+@@ -0,0 +1,4 @@
++apiVersion: v1
++data:
++  manifest: |
++    kind: Service
+diff -u -N /tmp/LIVE-1/v1.ConfigMap.test.pruned /tmp/MERGED-2/v1.ConfigMap.test.pruned
+--- /tmp/LIVE-1/v1.ConfigMap.test.pruned	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.ConfigMap.test.pruned	1970-01-01 09:00:00.000000000 +0900
+@@ -1,2 +0,0 @@
+-apiVersion: v1
+-kind: ConfigMap
+diff -u -N /tmp/LIVE-1/v1.ConfigMap.test.configured /tmp/MERGED-2/v1.ConfigMap.test.configured
+--- /tmp/LIVE-1/v1.ConfigMap.test.configured	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.ConfigMap.test.configured	2022-02-22 22:00:00.000000000 +0900
+@@ -2,3 +2,3 @@
+ data:
+-  manifest: |
+-    kind: Service
++  manifest: |
++    kind: Deployment";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual: Vec<(String, Action)> = parser
+            .parse(diff)
+            .unwrap()
+            .resources
+            .iter()
+            .map(|r| (r.name.clone(), r.action))
+            .collect();
+        assert_eq!(
+            actual,
+            vec![
+                ("configured".to_string(), Action::Configured),
+                ("created".to_string(), Action::Created),
+                ("pruned".to_string(), Action::Pruned),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_classifies_dev_null_sides() {
+        let diff = "--- /dev/null
++++ b/v1.Service.test.app
+@@ -1 +1 @@
+-a
++b";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources[0].action, Action::Created);
+    }
+
+    #[test]
+    fn test_parse_merges_pruned_and_created_resource_into_replaced() {
+        let diff = "--- /tmp/LIVE-1/v1.Service.test.app
++++ /dev/null
+@@ -1 +0,0 @@
+-spec: old
+--- /dev/null
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -0,0 +1 @@
++spec: new";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources.len(), 1);
+        assert_eq!(actual.resources[0].action, Action::Replaced);
+        assert_eq!(
+            actual.resources[0].diff(),
+            "@@ -1 +0,0 @@\n-spec: old\n@@ -0,0 +1 @@\n+spec: new"
+        );
+    }
+
+    #[test]
+    fn test_parse_diff_ru_n_output() {
+        let diff = "diff -ruN /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
+-  port: 80
++  port: 8080";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_dialect(DiffDialect::Unified);
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources.len(), 1);
+        assert_eq!(actual.resources[0].id(), "v1.Service.test.app");
+    }
+
+    #[test]
+    fn test_parse_skips_only_in_lines() {
+        let diff = "Only in /tmp/MERGED-2: v1.Service.test.new
+diff -u /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/MERGED-2/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
+-  port: 80
++  port: 8080";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources.len(), 1);
+        assert_eq!(actual.resources[0].name, "app");
+    }
+
+    #[test]
+    fn test_parse_git_style_output() {
+        let diff = "diff --git a/v1.Service.test.app b/v1.Service.test.app
+index 1234567..89abcde 100644
+--- a/v1.Service.test.app
++++ b/v1.Service.test.app
+@@ -1,2 +1,2 @@
+ spec:
+-  port: 80
++  port: 8080
+diff --git a/v1.ConfigMap.test.new b/v1.ConfigMap.test.new
+new file mode 100644
+index 0000000..89abcde
+--- /dev/null
++++ b/v1.ConfigMap.test.new
+@@ -0,0 +1 @@
++kind: ConfigMap";
+        for dialect in [DiffDialect::Auto, DiffDialect::Git] {
+            let parser = DiffParser::new(false, false, Vec::new())
+                .unwrap()
+                .with_dialect(dialect);
+            let actual = parser.parse(diff).unwrap();
+            let ids: Vec<(String, Action)> = actual
+                .resources
+                .iter()
+                .map(|r| (r.id(), r.action))
+                .collect();
+            assert_eq!(
+                ids,
+                vec![
+                    ("v1.ConfigMap.test.new".to_string(), Action::Created),
+                    ("v1.Service.test.app".to_string(), Action::Configured),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_git_dialect_rejects_unified_output() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -1 +1 @@
+-a
++b";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_dialect(DiffDialect::Git);
+        let actual = parser.parse(diff);
+        assert!(actual.unwrap_err().to_string().contains("diff --git"));
+    }
+
+    #[test]
+    fn test_parse_unified_dialect_rejects_git_output() {
+        let diff = "diff --git a/v1.Service.test.app b/v1.Service.test.app
+index 1234567..89abcde 100644
+--- a/v1.Service.test.app
++++ b/v1.Service.test.app
+@@ -1 +1 @@
+-a
++b";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_dialect(DiffDialect::Unified);
+        assert!(parser.parse(diff).is_err());
+    }
+
+    #[test]
+    fn test_parse_ansi_colored_output() {
+        let diff = "\x1b[1mdiff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app\x1b[0m
+\x1b[1m--- /tmp/LIVE-1/v1.Service.test.app\x1b[0m
+\x1b[1m+++ /tmp/MERGED-2/v1.Service.test.app\x1b[0m
+\x1b[36m@@ -1 +1 @@\x1b[0m
+\x1b[31m-  port: 80\x1b[0m
+\x1b[32m+  port: 8080\x1b[0m";
+        for dialect in [DiffDialect::Auto, DiffDialect::Ansi] {
+            let parser = DiffParser::new(false, false, Vec::new())
+                .unwrap()
+                .with_dialect(dialect);
+            let actual = parser.parse(diff).unwrap();
+            assert_eq!(actual.resources.len(), 1);
+            assert_eq!(
+                actual.resources[0].diff(),
+                "@@ -1 +1 @@\n-  port: 80\n+  port: 8080"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_with_semantic_diff_reports_path_changes() {
+        let diff = "diff -u -N /tmp/LIVE-1/apps.v1.Deployment.test.app /tmp/MERGED-2/apps.v1.Deployment.test.app
+--- /tmp/LIVE-1/apps.v1.Deployment.test.app
++++ /tmp/MERGED-2/apps.v1.Deployment.test.app
+@@ -1,6 +1,6 @@
+ spec:
+   template:
+     spec:
+       containers:
+       - name: app
+-        image: app:1
++        image: app:2";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_semantic_diff(true);
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(
+            actual.resources[0].changes,
+            Some(vec![semantic::PathChange {
+                path: "spec.template.spec.containers[name=app].image".to_string(),
+                before: Some("app:1".to_string()),
+                after: Some("app:2".to_string()),
+            }])
+        );
+
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources[0].changes, None);
+    }
+
+    #[test]
+    fn test_parse_with_semantic_diff_skips_partial_diffs() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -8 +8 @@
+-  port: 80
++  port: 8080";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_semantic_diff(true);
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources[0].changes, None);
+    }
+
+    #[test]
+    fn test_parse_with_semantic_diff_skips_partial_diffs_at_top() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Service.test.app /tmp/MERGED-2/v1.Service.test.app
+--- /tmp/LIVE-1/v1.Service.test.app
++++ /tmp/MERGED-2/v1.Service.test.app
+@@ -1,5 +1,5 @@
+ spec:
+-  port: 80
++  port: 8080
+   selector:
+     app: app
+   type: ClusterIP";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_semantic_diff(true);
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(actual.resources[0].changes, None);
+    }
+
+    #[test]
+    fn test_parse_redacts_secret_data_and_extra_keys() {
+        let diff = "diff -u -N /tmp/LIVE-1/v1.Secret.test.db /tmp/MERGED-2/v1.Secret.test.db
+--- /tmp/LIVE-1/v1.Secret.test.db
++++ /tmp/MERGED-2/v1.Secret.test.db
+@@ -1,2 +1,2 @@
+ data:
+-  password: cGFzcw==
++  password: bmV3
+diff -u -N /tmp/LIVE-1/v1.ConfigMap.test.conf /tmp/MERGED-2/v1.ConfigMap.test.conf
+--- /tmp/LIVE-1/v1.ConfigMap.test.conf
++++ /tmp/MERGED-2/v1.ConfigMap.test.conf
+@@ -1,2 +1,2 @@
+ data:
+-  API_TOKEN: abc
++  API_TOKEN: def";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_redact_keys(&["token".to_string()])
+            .unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert_eq!(
+            actual.resources[0].diff(),
+            "@@ -1,2 +1,2 @@\n data:\n-  API_TOKEN: ***\n+  API_TOKEN: *** (changed)"
+        );
+        assert_eq!(
+            actual.resources[1].diff(),
+            "@@ -1,2 +1,2 @@\n data:\n-  password: ***\n+  password: *** (changed)"
+        );
+    }
+
+    #[test]
+    fn test_diff_dialect_from_str() {
+        assert_eq!(DiffDialect::from_str("auto").unwrap(), DiffDialect::Auto);
+        assert_eq!(
+            DiffDialect::from_str("unified").unwrap(),
+            DiffDialect::Unified
+        );
+        assert_eq!(DiffDialect::from_str("git").unwrap(), DiffDialect::Git);
+        assert_eq!(DiffDialect::from_str("ansi").unwrap(), DiffDialect::Ansi);
+        assert!(DiffDialect::from_str("dyff").is_err());
+    }
+
+    #[test]
+    fn test_parse_with_unparsable_file_name_returns_error() {
+        let diff = "--- /tmp/LIVE-1/not-a-resource
++++ /tmp/MERGED-2/not-a-resource
+@@ -1 +1 @@
+-b
++c";
+        let parser = DiffParser::new(false, false, Vec::new()).unwrap();
+        assert!(parser.parse(diff).is_err());
     }
 
     #[test]
@@ -331,6 +1340,149 @@ hij";
         assert_eq!(actual, expected);
     }
 
+    fn preset_parser(presets: &[SuppressPreset]) -> DiffParser {
+        DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_presets(presets)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_remove_preset_fields_removes_helm_labels() {
+        let diff = "
+ @@ -3,9 +3,9 @@
+   annotations:
+-    meta.helm.sh/release-name: app-1
++    meta.helm.sh/release-name: app-2
+     meta.helm.sh/release-namespace: test
+   labels:
+     app: test-app
+-    app.kubernetes.io/managed-by: Helm
+-    helm.sh/chart: app-1.0.0
++    helm.sh/chart: app-1.1.0
+   name: test-app
+";
+        let parser = preset_parser(&[SuppressPreset::Helm]);
+        let actual = parser.remove_preset_fields(diff);
+        let expected = "
+ @@ -3,9 +3,9 @@
+   annotations:
+   labels:
+     app: test-app
+   name: test-app
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_preset_fields_removes_flux_labels() {
+        let diff = "
+ @@ -3,6 +3,6 @@
+   labels:
+     app: test-app
+-    kustomize.toolkit.fluxcd.io/name: apps
++    kustomize.toolkit.fluxcd.io/name: apps-v2
+     kustomize.toolkit.fluxcd.io/namespace: flux-system
+   name: test-app
+";
+        let parser = preset_parser(&[SuppressPreset::Flux]);
+        let actual = parser.remove_preset_fields(diff);
+        let expected = "
+ @@ -3,6 +3,6 @@
+   labels:
+     app: test-app
+   name: test-app
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_preset_fields_removes_kustomize_origin_with_block_value() {
+        let diff = "
+ @@ -2,8 +2,8 @@
+   annotations:
+-    config.kubernetes.io/origin: |
+-      path: base/deployment.yaml
++    config.kubernetes.io/origin: |
++      path: overlays/dev/deployment.yaml
+     owner: team
+   name: test-app
+";
+        let parser = preset_parser(&[SuppressPreset::Kustomize]);
+        let actual = parser.remove_preset_fields(diff);
+        let expected = "
+ @@ -2,8 +2,8 @@
+   annotations:
+     owner: team
+   name: test-app
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_preset_fields_removes_istio_status_annotations() {
+        let diff = r#"
+ @@ -20,6 +20,6 @@
+       annotations:
+-        sidecar.istio.io/status: '{"initContainers":["istio-init"],"revision":"1-20"}'
++        sidecar.istio.io/status: '{"initContainers":["istio-init"],"revision":"1-21"}'
+         kubectl.kubernetes.io/default-container: app
+       labels:
+"#;
+        let parser = preset_parser(&[SuppressPreset::Istio]);
+        let actual = parser.remove_preset_fields(diff);
+        let expected = "
+ @@ -20,6 +20,6 @@
+       annotations:
+       labels:
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_preset_fields_do_nothing() {
+        let diff = "
+ @@ -3,6 +3,6 @@
+   labels:
+     app: test-app
+-    helm.sh/chart: app-1.0.0
++    helm.sh/chart: app-1.1.0
+     kustomize.toolkit.fluxcd.io/name: apps
+";
+        let actual = preset_parser(&[]).remove_preset_fields(diff);
+        assert_eq!(actual, diff);
+
+        // only the lines of the selected presets are removed
+        let actual = preset_parser(&[SuppressPreset::Istio]).remove_preset_fields(diff);
+        assert_eq!(actual, diff);
+    }
+
+    #[test]
+    fn test_parse_with_suppress_presets_excludes_preset_only_resource() {
+        let diff = "diff -u -N /tmp/v1.Service.default.app /tmp/v1.Service.default.app
+--- /tmp/v1.Service.default.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/v1.Service.default.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
+-    helm.sh/chart: app-1.0.0
++    helm.sh/chart: app-1.1.0";
+        let parser = preset_parser(&[SuppressPreset::Helm, SuppressPreset::Flux]);
+        let actual = parser.parse(diff).unwrap();
+        assert!(actual.resources.is_empty());
+    }
+
+    #[test]
+    fn test_suppress_preset_from_str() {
+        assert_eq!(
+            SuppressPreset::from_str("helm").unwrap(),
+            SuppressPreset::Helm
+        );
+        assert_eq!(
+            SuppressPreset::from_str("istio").unwrap(),
+            SuppressPreset::Istio
+        );
+        assert!(SuppressPreset::from_str("skaffold").is_err());
+    }
+
     #[test]
     fn test_remove_generation_fields_removes_generation_fields() {
         let diff = "
@@ -446,10 +1598,13 @@ hij";
 
     #[test]
     fn test_suppress_by_skaffold_removes_entry_when_only_skaffold_diff() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-    skaffold.dev/run-id: 123\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-    skaffold.dev/run-id: 123\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(true, false, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_skaffold_labels(d));
         assert!(actual.is_empty());
@@ -457,22 +1612,28 @@ hij";
 
     #[test]
     fn test_suppress_by_skaffold_keeps_entry_when_other_diff_exists() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-    skaffold.dev/run-id: 123\n-  image: old\n+  image: new\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-    skaffold.dev/run-id: 123\n-  image: old\n+  image: new\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(true, false, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_skaffold_labels(d));
         assert_eq!(actual.len(), 1);
-        assert!(actual["v1.Deployment.default.app"].contains("-  image: old"));
+        assert!(actual[0].diff().contains("-  image: old"));
     }
 
     #[test]
     fn test_suppress_by_argocd_removes_entry_when_only_argocd_diff() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-                argocd.argoproj.io/tracking-id: release-name:app\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-                argocd.argoproj.io/tracking-id: release-name:app\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(false, true, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_argocd_annotations(d));
         assert!(actual.is_empty());
@@ -480,22 +1641,22 @@ hij";
 
     #[test]
     fn test_suppress_by_argocd_keeps_entry_when_other_diff_exists() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-                argocd.argoproj.io/tracking-id: release-name:app\n-  image: old\n+  image: new\n".to_string(),
-        )]);
+        let result = vec![ResourceChange::new("v1.Deployment.default.app", "-                argocd.argoproj.io/tracking-id: release-name:app\n-  image: old\n+  image: new\n").unwrap()];
         let parser = DiffParser::new(false, true, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_argocd_annotations(d));
         assert_eq!(actual.len(), 1);
-        assert!(actual["v1.Deployment.default.app"].contains("-  image: old"));
+        assert!(actual[0].diff().contains("-  image: old"));
     }
 
     #[test]
     fn test_suppress_by_generation_removes_entry_when_only_generation_diff() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-  generation: 18\n+  generation: 19\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-  generation: 18\n+  generation: 19\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(false, false, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_generation_fields(d));
         assert!(actual.is_empty());
@@ -503,22 +1664,28 @@ hij";
 
     #[test]
     fn test_suppress_by_generation_keeps_entry_when_other_diff_exists() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-  generation: 18\n+  generation: 19\n-  image: old\n+  image: new\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-  generation: 18\n+  generation: 19\n-  image: old\n+  image: new\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(false, false, Vec::new()).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_generation_fields(d));
         assert_eq!(actual.len(), 1);
-        assert!(actual["v1.Deployment.default.app"].contains("-  image: old"));
+        assert!(actual[0].diff().contains("-  image: old"));
     }
 
     #[test]
     fn test_suppress_by_image_tags_removes_entry_when_only_image_tag_diff() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-            image: myapp:v1.0\n+            image: myapp:v2.0\n".to_string(),
-        )]);
+        let result = vec![
+            ResourceChange::new(
+                "v1.Deployment.default.app",
+                "-            image: myapp:v1.0\n+            image: myapp:v2.0\n",
+            )
+            .unwrap(),
+        ];
         let parser = DiffParser::new(false, false, vec!["myapp".to_string()]).unwrap();
         let actual = parser.suppress_by(result, |d| parser.remove_image_tags(d, "myapp"));
         assert!(actual.is_empty());
@@ -526,10 +1693,7 @@ hij";
 
     #[test]
     fn test_suppress_by_image_tags_applies_multiple_images() {
-        let result = HashMap::from([(
-            "v1.Deployment.default.app".to_string(),
-            "-            image: app1:v1\n+            image: app1:v2\n-            image: app2:v1\n+            image: app2:v2\n".to_string(),
-        )]);
+        let result = vec![ResourceChange::new("v1.Deployment.default.app", "-            image: app1:v1\n+            image: app1:v2\n-            image: app2:v1\n+            image: app2:v2\n").unwrap()];
         let parser =
             DiffParser::new(false, false, vec!["app1".to_string(), "app2".to_string()]).unwrap();
         let mut actual = result;
@@ -544,10 +1708,11 @@ hij";
         let diff = "diff -u -N /tmp/v1.Deployment.default.app /tmp/v1.Deployment.default.app
 --- /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
 +++ /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +0,0 @@
 -    skaffold.dev/run-id: abc123";
         let parser = DiffParser::new(true, false, Vec::new()).unwrap();
         let actual = parser.parse(diff).unwrap();
-        assert!(actual.kind_result.is_empty());
+        assert!(actual.resources.is_empty());
     }
 
     #[test]
@@ -555,10 +1720,11 @@ hij";
         let diff = "diff -u -N /tmp/v1.Deployment.default.app /tmp/v1.Deployment.default.app
 --- /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
 +++ /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +0,0 @@
 -                argocd.argoproj.io/tracking-id: release-name:app";
         let parser = DiffParser::new(false, true, Vec::new()).unwrap();
         let actual = parser.parse(diff).unwrap();
-        assert!(actual.kind_result.is_empty());
+        assert!(actual.resources.is_empty());
     }
 
     #[test]
@@ -566,11 +1732,12 @@ hij";
         let diff = "diff -u -N /tmp/v1.Deployment.default.app /tmp/v1.Deployment.default.app
 --- /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
 +++ /tmp/v1.Deployment.default.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
 -            image: myapp:v1.0
 +            image: myapp:v2.0";
         let parser = DiffParser::new(false, false, vec!["myapp".to_string()]).unwrap();
         let actual = parser.parse(diff).unwrap();
-        assert!(actual.kind_result.is_empty());
+        assert!(actual.resources.is_empty());
     }
 
     #[test]
@@ -578,14 +1745,16 @@ hij";
         let diff = "diff -u -N /tmp/v1.Service.test.app /tmp/v1.Service.test.app
 --- /tmp/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
 +++ /tmp/v1.Service.test.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1 +1 @@
 - name: DELIMITER
 + name: DELIMITER_NEW";
         let parser = DiffParser::new(false, false, Vec::new()).unwrap();
         let actual = parser.parse(diff).unwrap();
-        assert_eq!(actual.kind_result.len(), 1);
+        assert_eq!(actual.resources.len(), 1);
+        assert_eq!(actual.resources[0].id(), "v1.Service.test.app");
         assert_eq!(
-            actual.kind_result["v1.Service.test.app"],
-            "- name: DELIMITER\n+ name: DELIMITER_NEW"
+            actual.resources[0].diff(),
+            "@@ -1 +1 @@\n- name: DELIMITER\n+ name: DELIMITER_NEW"
         );
     }
 
@@ -593,6 +1762,253 @@ hij";
     fn test_parse_empty_diff_returns_empty_result() {
         let parser = DiffParser::new(false, false, Vec::new()).unwrap();
         let actual = parser.parse("").unwrap();
-        assert!(actual.kind_result.is_empty());
+        assert!(actual.resources.is_empty());
+    }
+
+    fn rule(line: Option<&str>, path: Option<&str>) -> SuppressRule {
+        SuppressRule {
+            name: "test".to_string(),
+            line: line.map(String::from),
+            path: path.map(String::from),
+            selector: ResourceSelector::default(),
+            drop: true,
+        }
+    }
+
+    #[test]
+    fn test_parse_yaml_path_splits_segments() {
+        assert_eq!(
+            DiffParser::parse_yaml_path("metadata.labels").unwrap(),
+            vec!["metadata", "labels"]
+        );
+        assert_eq!(
+            DiffParser::parse_yaml_path(r#"metadata.annotations."helm.sh/chart""#).unwrap(),
+            vec!["metadata", "annotations", "helm.sh/chart"]
+        );
+        assert_eq!(
+            DiffParser::parse_yaml_path(r#"metadata.annotations["helm.sh/chart"]"#).unwrap(),
+            vec!["metadata", "annotations", "helm.sh/chart"]
+        );
+    }
+
+    #[test]
+    fn test_parse_yaml_path_rejects_invalid_path() {
+        assert!(DiffParser::parse_yaml_path("").is_err());
+        assert!(DiffParser::parse_yaml_path("metadata..labels").is_err());
+        assert!(DiffParser::parse_yaml_path(r#"metadata."helm.sh/chart"#).is_err());
+    }
+
+    #[test]
+    fn test_remove_matching_lines_removes_changed_lines_only() {
+        let diff = "@@ -1,3 +1,3 @@
+ metadata:
+   labels:
+-    helm.sh/chart: app-1.0.0
++    helm.sh/chart: app-1.1.0
+     helm.sh/chart-owner: team";
+        let re = Regex::new("helm.sh/chart: ").unwrap();
+        let actual = DiffParser::remove_matching_lines(diff, &re);
+        let expected = "@@ -1,3 +1,3 @@
+ metadata:
+   labels:
+     helm.sh/chart-owner: team";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_lines_under_path_removes_nested_block() {
+        let diff = r#"@@ -1,9 +1,9 @@
+ metadata:
+   annotations:
+-    kubectl.kubernetes.io/last-applied-configuration: |
+-      {"apiVersion":"v1","kind":"Service"}
++    kubectl.kubernetes.io/last-applied-configuration: |
++      {"apiVersion":"v1","kind":"Service","spec":{}}
+     owner: team
+ spec:
+-  last-applied-configuration: keep"#;
+        let path = DiffParser::parse_yaml_path(
+            r#"metadata.annotations."kubectl.kubernetes.io/last-applied-configuration""#,
+        )
+        .unwrap();
+        let actual = DiffParser::remove_lines_under_path(diff, &path);
+        let expected = "@@ -1,9 +1,9 @@
+ metadata:
+   annotations:
+     owner: team
+ spec:
+-  last-applied-configuration: keep";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_lines_under_path_matches_hunk_starting_in_the_middle() {
+        // the hunk does not show "metadata:", so the path is matched from "labels:"
+        let diff = "@@ -12,4 +12,4 @@
+   labels:
+     app: web
+-    helm.sh/chart: web-1.0.0
++    helm.sh/chart: web-1.1.0
+@@ -30,4 +30,4 @@
+       containers:
+       - name: web
+-        image: web:1
++        image: web:2";
+        let path = DiffParser::parse_yaml_path(r#"metadata.labels."helm.sh/chart""#).unwrap();
+        let actual = DiffParser::remove_lines_under_path(diff, &path);
+        let expected = "@@ -12,4 +12,4 @@
+   labels:
+     app: web
+@@ -30,4 +30,4 @@
+       containers:
+       - name: web
+-        image: web:1
++        image: web:2";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_remove_lines_under_path_keeps_list_items_under_their_key() {
+        let diff = "@@ -1,5 +1,5 @@
+ spec:
+   args:
+   - --verbose
+-  - --level=1
++  - --level=2";
+        let path = DiffParser::parse_yaml_path("spec.args").unwrap();
+        let actual = DiffParser::remove_lines_under_path(diff, &path);
+        assert_eq!(actual, "@@ -1,5 +1,5 @@\n spec:\n   args:\n   - --verbose");
+    }
+
+    #[test]
+    fn test_suppress_rules_drop_resource_only_with_suppressed_diff() {
+        let result = vec![
+            ResourceChange::new(
+                "v1.ConfigMap.default.a",
+                "-    helm.sh/chart: a-1\n+    helm.sh/chart: a-2",
+            )
+            .unwrap(),
+            ResourceChange::new(
+                "v1.ConfigMap.default.b",
+                "-    helm.sh/chart: b-1\n+    helm.sh/chart: b-2\n-  key: 1\n+  key: 2",
+            )
+            .unwrap(),
+        ];
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_rules(&[rule(Some("helm.sh/chart: "), None)])
+            .unwrap();
+        let actual = parser.suppress_by_rule(result, &parser.suppressions[0]);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].name, "b");
+        assert_eq!(actual[0].diff(), "-  key: 1\n+  key: 2");
+    }
+
+    #[test]
+    fn test_suppress_rules_keep_resource_without_drop() {
+        let result = vec![
+            ResourceChange::new(
+                "v1.ConfigMap.default.a",
+                "-    helm.sh/chart: a-1\n+    helm.sh/chart: a-2",
+            )
+            .unwrap(),
+        ];
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_rules(&[SuppressRule {
+                drop: false,
+                ..rule(Some("helm.sh/chart: "), None)
+            }])
+            .unwrap();
+        let actual = parser.suppress_by_rule(result, &parser.suppressions[0]);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].stats, LineStats::default());
+    }
+
+    #[test]
+    fn test_suppress_rules_apply_to_selected_resources_only() {
+        let diff = "-    helm.sh/chart: a-1\n+    helm.sh/chart: a-2";
+        let result = vec![
+            ResourceChange::new("v1.ConfigMap.default.a", diff).unwrap(),
+            ResourceChange::new("v1.Secret.default.a", diff).unwrap(),
+        ];
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_rules(&[SuppressRule {
+                selector: ResourceSelector {
+                    kind: Some("ConfigMap".to_string()),
+                    ..Default::default()
+                },
+                ..rule(Some("helm.sh/chart: "), None)
+            }])
+            .unwrap();
+        let actual = parser.suppress_by_rule(result, &parser.suppressions[0]);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].kind, "Secret");
+    }
+
+    #[test]
+    fn test_suppress_rules_do_not_drop_unselected_resources() {
+        // the Service has only context lines left, e.g. by a previous rule without drop
+        let service = "@@ -1,2 +1,2 @@\n metadata:\n   name: a";
+        let result = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.a",
+                "-    helm.sh/chart: a-1\n+    helm.sh/chart: a-2",
+            )
+            .unwrap(),
+            ResourceChange::new("v1.Service.default.a", service).unwrap(),
+        ];
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_rules(&[SuppressRule {
+                selector: ResourceSelector {
+                    kind: Some("Deployment".to_string()),
+                    ..Default::default()
+                },
+                ..rule(Some("helm.sh/chart: "), None)
+            }])
+            .unwrap();
+        let actual = parser.suppress_by_rule(result, &parser.suppressions[0]);
+        assert_eq!(actual.len(), 1);
+        assert_eq!(actual[0].kind, "Service");
+        assert_eq!(actual[0].diff(), service);
+    }
+
+    #[test]
+    fn test_parse_with_suppress_rules_by_path() {
+        let diff = "diff -u -N /tmp/v1.Service.default.app /tmp/v1.Service.default.app
+--- /tmp/v1.Service.default.app	2022-02-22 22:00:00.000000000 +0900
++++ /tmp/v1.Service.default.app	2022-02-22 22:00:00.000000000 +0900
+@@ -1,3 +1,3 @@
+ metadata:
+   annotations:
+-    kubectl.kubernetes.io/last-applied-configuration: '{}'
++    kubectl.kubernetes.io/last-applied-configuration: '{\"a\":1}'";
+        let parser = DiffParser::new(false, false, Vec::new())
+            .unwrap()
+            .with_suppress_rules(&[rule(
+                None,
+                Some(r#"metadata.annotations."kubectl.kubernetes.io/last-applied-configuration""#),
+            )])
+            .unwrap();
+        let actual = parser.parse(diff).unwrap();
+        assert!(actual.resources.is_empty());
+    }
+
+    #[test]
+    fn test_with_suppress_rules_rejects_invalid_rules() {
+        let parser = || DiffParser::new(false, false, Vec::new()).unwrap();
+        assert!(parser().with_suppress_rules(&[rule(None, None)]).is_err());
+        assert!(
+            parser()
+                .with_suppress_rules(&[rule(Some("a"), Some("b"))])
+                .is_err()
+        );
+        assert!(
+            parser()
+                .with_suppress_rules(&[rule(Some("("), None)])
+                .is_err()
+        );
     }
 }
@@ -0,0 +1,396 @@
+use crate::resource::{Hunk, ResourceChange};
+
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+// matches a YAML mapping entry like "  password: abc", "- name: TOKEN" or "  key: |"
+static ENTRY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?P<prefix>(?P<indent> *)(?P<item>(?:- )*)(?P<key>"[^"]*"|'[^']*'|[^\s"'#{\[][^:#]*?):)(?:(?P<sep>\s+)(?P<value>.*))?$"#)
+        .unwrap()
+});
+
+/// Keys of Secret metadata holding a copy of the data, e.g. the last applied manifest in JSON.
+const SECRET_METADATA_KEYS: [&str; 2] = [
+    "kubectl.kubernetes.io/last-applied-configuration",
+    "managedFields",
+];
+
+const MASK: &str = "***";
+const CHANGED_MASK: &str = "*** (changed)";
+
+/// Masks secret values in diffs so that they do not land in the comment.
+/// Values under `data`/`stringData` of `v1.Secret` are always masked, and so are the copies of
+/// them in the last-applied-configuration annotation and `managedFields`. Values of keys matching
+/// the extra patterns (e.g. `password`) are masked in any resource, including env vars given as
+/// `- name: DB_PASSWORD` and `value: ...`.
+#[derive(Debug, Default)]
+pub struct Redactor {
+    keys: Option<Regex>,
+}
+
+/// What a hunk line turns into.
+struct Masked {
+    /// Identifies the value to tell if it is changed between removed and added lines.
+    id: Option<String>,
+    value: String,
+    /// Line with the value masked.
+    line: String,
+}
+
+/// Position in a hunk while masking it.
+#[derive(Default)]
+struct State {
+    /// Whether the lines are under `data` or `stringData` of a Secret. `None` until a top-level key
+    /// is seen, as a hunk may start in the middle of the data.
+    in_secret_data: Option<bool>,
+    /// Indentation of the key whose block value is masked.
+    block_indent: Option<usize>,
+    /// Indentation of the `value:` key of an env var whose name matches the patterns.
+    env_value_indent: Option<usize>,
+}
+
+impl Redactor {
+    /// `keys` are case-insensitive regexes matched against mapping keys and env var names.
+    pub fn new(keys: &[String]) -> Result<Self> {
+        if keys.is_empty() {
+            return Ok(Self::default());
+        }
+        let pattern = keys.iter().map(|k| format!("(?:{k})")).collect::<Vec<_>>();
+        let keys = RegexBuilder::new(&pattern.join("|"))
+            .case_insensitive(true)
+            .build()?;
+        Ok(Self { keys: Some(keys) })
+    }
+
+    pub fn redact(&self, change: ResourceChange) -> ResourceChange {
+        let is_secret =
+            change.group.is_empty() && change.version == "v1" && change.kind == "Secret";
+        if !is_secret && self.keys.is_none() {
+            return change;
+        }
+        let hunks = change
+            .hunks
+            .into_iter()
+            .map(|hunk| self.redact_hunk(hunk, is_secret))
+            .collect();
+        ResourceChange { hunks, ..change }
+    }
+
+    fn redact_hunk(&self, hunk: Hunk, is_secret: bool) -> Hunk {
+        let mut state = State::default();
+        let masked: Vec<Option<Masked>> = hunk
+            .lines
+            .iter()
+            .map(|line| self.mask_line(line, is_secret, &mut state))
+            .collect();
+
+        // values of removed lines to tell if the added ones are changed
+        let removed: HashMap<&str, &str> = hunk
+            .lines
+            .iter()
+            .zip(&masked)
+            .filter(|(line, _)| line.starts_with('-'))
+            .filter_map(|(_, m)| m.as_ref())
+            .filter_map(|m| Some((m.id.as_deref()?, m.value.as_str())))
+            .collect();
+
+        let lines = hunk
+            .lines
+            .iter()
+            .zip(&masked)
+            .map(|(line, m)| {
+                m.as_ref().map_or_else(
+                    || line.clone(),
+                    |m| {
+                        let changed = line.starts_with('+')
+                            && m.id
+                                .as_deref()
+                                .and_then(|id| removed.get(id))
+                                .is_some_and(|&old| old != m.value);
+                        if changed {
+                            m.line.replacen(MASK, CHANGED_MASK, 1)
+                        } else {
+                            m.line.clone()
+                        }
+                    },
+                )
+            })
+            .collect();
+        Hunk { lines, ..hunk }
+    }
+
+    /// Mask the value of a hunk line. Returns `None` if the line is kept as it is.
+    fn mask_line(&self, line: &str, is_secret: bool, state: &mut State) -> Option<Masked> {
+        let marker = line.get(..1)?;
+        if !matches!(marker, "+" | "-" | " ") {
+            return None;
+        }
+        let content = &line[1..];
+        if content.trim().is_empty() {
+            return None;
+        }
+        let indent = content.len() - content.trim_start().len();
+
+        // lines of a masked block value
+        if let Some(block_indent) = state.block_indent {
+            // items of a sequence may be at the same indentation as their key
+            let is_item = indent == block_indent && content.trim_start().starts_with("- ");
+            if indent > block_indent || is_item {
+                return Some(Masked {
+                    id: None,
+                    value: String::new(),
+                    line: format!("{marker}{}{MASK}", " ".repeat(indent)),
+                });
+            }
+            state.block_indent = None;
+        }
+        if state.env_value_indent.is_some_and(|i| indent < i) {
+            state.env_value_indent = None;
+        }
+
+        let caps = ENTRY.captures(content)?;
+        let key = caps["key"].trim_matches(['"', '\'']);
+        let key_indent = caps["indent"].len() + caps["item"].len();
+        let value = caps.name("value").map_or("", |m| m.as_str());
+        if indent == 0 {
+            state.in_secret_data = Some(matches!(key, "data" | "stringData"));
+            return None;
+        }
+
+        let id = if is_secret && state.in_secret_data != Some(false) {
+            Some(format!("data:{key}"))
+        } else if self.matches_key(key) || (is_secret && SECRET_METADATA_KEYS.contains(&key)) {
+            Some(format!("{key_indent}:{key}"))
+        } else if state.env_value_indent == Some(key_indent) && key == "value" {
+            Some(format!("{key_indent}:env"))
+        } else {
+            if key == "name" && !caps["item"].is_empty() && self.matches_key(value) {
+                state.env_value_indent = Some(key_indent);
+            }
+            return None;
+        };
+        if value.is_empty() {
+            // nested mapping, e.g. the value of a matching key is a mapping
+            state.block_indent = Some(key_indent);
+            return None;
+        }
+        if value.starts_with(['|', '>']) {
+            state.block_indent = Some(key_indent);
+            return None;
+        }
+        Some(Masked {
+            id,
+            value: value.to_string(),
+            line: format!("{marker}{} {MASK}", &caps["prefix"]),
+        })
+    }
+
+    fn matches_key(&self, key: &str) -> bool {
+        self.keys.as_ref().is_some_and(|re| re.is_match(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redact(redactor: &Redactor, id: &str, diff: &str) -> String {
+        redactor
+            .redact(ResourceChange::new(id, diff).unwrap())
+            .diff()
+    }
+
+    #[test]
+    fn test_redact_masks_secret_data() {
+        let diff = "@@ -1,6 +1,6 @@
+ apiVersion: v1
+ data:
+-  password: cGFzcw==
++  password: bmV3cGFzcw==
+   username: YWRtaW4=
++  token: dG9rZW4=
+ kind: Secret";
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        let expected = "@@ -1,6 +1,6 @@
+ apiVersion: v1
+ data:
+-  password: ***
++  password: *** (changed)
+   username: ***
++  token: ***
+ kind: Secret";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_masks_string_data_block_values() {
+        let diff = "@@ -1,5 +1,5 @@
+ stringData:
+   config.yaml: |
+-    password: old
++    password: new
+ type: Opaque";
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        let expected = "@@ -1,5 +1,5 @@
+ stringData:
+   config.yaml: |
+-    ***
++    ***
+ type: Opaque";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_masks_hunk_starting_in_secret_data() {
+        // the hunk does not show "data:", so the values are masked until a top-level key
+        let diff = "@@ -3,4 +3,4 @@
+   a: YQ==
+-  b: Yg==
++  b: Yw==
+ kind: Secret
+ metadata:
+   name: db";
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        let expected = "@@ -3,4 +3,4 @@
+   a: ***
+-  b: ***
++  b: *** (changed)
+ kind: Secret
+ metadata:
+   name: db";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_keeps_secret_metadata_and_other_resources() {
+        let diff = "@@ -1,4 +1,4 @@
+ metadata:
+-  name: a
++  name: b
+ type: Opaque";
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        assert_eq!(actual, diff);
+
+        let diff = "@@ -1,3 +1,3 @@
+ data:
+-  password: a
++  password: b";
+        let actual = redact(&Redactor::default(), "v1.ConfigMap.default.conf", diff);
+        assert_eq!(actual, diff);
+    }
+
+    #[test]
+    fn test_redact_masks_secret_data_copies_in_metadata() {
+        let diff = r#"@@ -1,20 +1,20 @@
+ apiVersion: v1
+ data:
+-  password: b2xk
++  password: bmV3
+ kind: Secret
+ metadata:
+   annotations:
+     kubectl.kubernetes.io/last-applied-configuration: |
+-      {"apiVersion":"v1","data":{"password":"b2xk"},"kind":"Secret"}
++      {"apiVersion":"v1","data":{"password":"bmV3"},"kind":"Secret"}
+   managedFields:
+   - apiVersion: v1
+     fieldsV1:
+       f:data:
+         f:password: {}
+     manager: kubectl-client-side-apply
+   name: db
+ type: Opaque"#;
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        let expected = "@@ -1,20 +1,20 @@
+ apiVersion: v1
+ data:
+-  password: ***
++  password: *** (changed)
+ kind: Secret
+ metadata:
+   annotations:
+     kubectl.kubernetes.io/last-applied-configuration: |
+-      ***
++      ***
+   managedFields:
+   ***
+     ***
+       ***
+         ***
+     ***
+   name: db
+ type: Opaque";
+        assert_eq!(actual, expected);
+        assert!(!actual.contains("b2xk") && !actual.contains("bmV3"));
+
+        let diff = r#"@@ -1,3 +1,3 @@
+ metadata:
+   annotations:
+-    kubectl.kubernetes.io/last-applied-configuration: '{"data":{"password":"b2xk"}}'
++    kubectl.kubernetes.io/last-applied-configuration: '{"data":{"password":"bmV3"}}'"#;
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        let expected = "@@ -1,3 +1,3 @@
+ metadata:
+   annotations:
+-    kubectl.kubernetes.io/last-applied-configuration: ***
++    kubectl.kubernetes.io/last-applied-configuration: *** (changed)";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_masks_extra_keys_in_any_resource() {
+        let redactor = Redactor::new(&["password".to_string(), "^token$".to_string()]).unwrap();
+        let diff = "@@ -1,5 +1,5 @@
+ data:
+-  DB_PASSWORD: a
++  DB_PASSWORD: b
+   token: t
+   tokens: x";
+        let actual = redact(&redactor, "v1.ConfigMap.default.conf", diff);
+        let expected = "@@ -1,5 +1,5 @@
+ data:
+-  DB_PASSWORD: ***
++  DB_PASSWORD: *** (changed)
+   token: ***
+   tokens: x";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_masks_env_var_values() {
+        let redactor = Redactor::new(&["password".to_string()]).unwrap();
+        let diff = "@@ -10,6 +10,6 @@
+         env:
+         - name: DB_PASSWORD
+-          value: old
++          value: new
+         - name: DB_USER
+           value: admin";
+        let actual = redact(&redactor, "apps.v1.Deployment.default.app", diff);
+        let expected = "@@ -10,6 +10,6 @@
+         env:
+         - name: DB_PASSWORD
+-          value: ***
++          value: *** (changed)
+         - name: DB_USER
+           value: admin";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_redact_does_not_mark_unchanged_values() {
+        let diff = "@@ -1,3 +1,3 @@
+ data:
+-  a: YQ==
++  a: YQ==";
+        let actual = redact(&Redactor::default(), "v1.Secret.default.db", diff);
+        assert_eq!(actual, "@@ -1,3 +1,3 @@\n data:\n-  a: ***\n+  a: ***");
+    }
+
+    #[test]
+    fn test_new_with_invalid_pattern_returns_error() {
+        assert!(Redactor::new(&["(".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,215 @@
+mod junit;
+mod sarif;
+mod terminal;
+
+use crate::resource::{LineStats, ResourceChange};
+use crate::template::{Counts, ResourceContext};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use strum_macros::EnumString;
+
+/// Format of the output written to stdout.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, EnumString, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Nothing but the comment posted to the CI platform, or the Markdown in local mode.
+    #[default]
+    #[strum(serialize = "markdown")]
+    Markdown,
+
+    /// The parse result in JSON for other tools, e.g. policy checks or dashboards.
+    #[strum(serialize = "json")]
+    Json,
+}
+
+/// Whether to color the output for a terminal in local mode.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, EnumString, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Colored text if stdout is a terminal, Markdown otherwise.
+    #[default]
+    #[strum(serialize = "auto")]
+    Auto,
+
+    /// Colored text even if stdout is not a terminal.
+    #[strum(serialize = "always")]
+    Always,
+
+    /// Plain text without colors.
+    #[strum(serialize = "never")]
+    Never,
+}
+
+/// Machine-readable parse result.
+#[derive(Serialize, Debug)]
+pub struct Report {
+    target: Option<String>,
+    link: String,
+    counts: Counts,
+    stats: LineStats,
+    resources: Vec<ResourceContext>,
+    /// Resources filtered out by `--include`/`--exclude`.
+    ignored: Vec<ResourceContext>,
+}
+
+impl Report {
+    pub fn new(
+        resources: &[ResourceChange],
+        ignored: &[ResourceChange],
+        link: String,
+        target: Option<String>,
+    ) -> Self {
+        Self {
+            target,
+            link,
+            counts: Counts::new(resources, ignored),
+            stats: resources.iter().map(|r| r.stats).sum(),
+            resources: resources.iter().map(ResourceContext::from).collect(),
+            ignored: ignored.iter().map(ResourceContext::from).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Plain text for a terminal, with ANSI colors if `color` is set.
+    pub fn to_terminal(&self, color: bool) -> String {
+        terminal::render(self, color)
+    }
+
+    /// JUnit XML with a failed test case per changed resource.
+    pub fn to_junit_xml(&self) -> String {
+        junit::render(self)
+    }
+
+    /// SARIF 2.1.0 log with a result per changed resource.
+    pub fn to_sarif(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&sarif::render(self))?)
+    }
+
+    /// Write the JUnit XML and the SARIF reports to the given paths.
+    pub fn write_files(&self, junit: Option<&Path>, sarif: Option<&Path>) -> Result<()> {
+        if let Some(path) = junit {
+            fs::write(path, self.to_junit_xml())
+                .with_context(|| format!("failed to write JUnit report: {}", path.display()))?;
+        }
+        if let Some(path) = sarif {
+            fs::write(path, self.to_sarif()?)
+                .with_context(|| format!("failed to write SARIF report: {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!(OutputFormat::from_str("json").unwrap(), OutputFormat::Json);
+        assert_eq!(
+            OutputFormat::from_str("markdown").unwrap(),
+            OutputFormat::Markdown
+        );
+        assert!(OutputFormat::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn test_color_mode_from_str() {
+        assert_eq!(ColorMode::from_str("always").unwrap(), ColorMode::Always);
+        assert_eq!(ColorMode::from_str("never").unwrap(), ColorMode::Never);
+        assert_eq!(ColorMode::from_str("auto").unwrap(), ColorMode::Auto);
+        assert!(ColorMode::from_str("yes").is_err());
+    }
+
+    #[test]
+    fn test_report_counts_resources_by_action() {
+        let resources = vec![
+            ResourceChange::new("v1.Service.default.a", " a\n-b\n+c").unwrap(),
+            ResourceChange::new("v1.Service.default.b", "+a\n+b").unwrap(),
+        ];
+        let ignored = vec![ResourceChange::new("v1.Secret.default.s", "-a").unwrap()];
+        let report = Report::new(&resources, &ignored, String::new(), None);
+        assert_eq!(
+            report.counts,
+            Counts {
+                created: 1,
+                configured: 1,
+                ignored: 1,
+                total: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            report.stats,
+            LineStats {
+                added: 3,
+                removed: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_report_to_json() {
+        let resources = vec![ResourceChange::new("apps.v1.Deployment.prod.app", "-a\n+b").unwrap()];
+        let report = Report::new(
+            &resources,
+            &[],
+            "https://example.com/job".to_string(),
+            Some("prod".to_string()),
+        );
+        let actual: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        let expected = serde_json::json!({
+            "target": "prod",
+            "link": "https://example.com/job",
+            "counts": {"created": 0, "pruned": 0, "replaced": 0, "configured": 1, "ignored": 0, "total": 1},
+            "stats": {"added": 1, "removed": 1},
+            "resources": [{
+                "id": "apps.v1.Deployment.prod.app",
+                "group": "apps",
+                "version": "v1",
+                "kind": "Deployment",
+                "namespace": "prod",
+                "name": "app",
+                "action": "configured",
+                "added": 1,
+                "removed": 1,
+                "diff": "-a\n+b",
+            }],
+            "ignored": [],
+        });
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_write_files() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let junit = temp_dir.path().join("junit.xml");
+        let sarif = temp_dir.path().join("ksnotify.sarif");
+        let resources = vec![ResourceChange::new("v1.Service.default.a", "-a").unwrap()];
+        let report = Report::new(&resources, &[], String::new(), None);
+        report.write_files(Some(&junit), Some(&sarif)).unwrap();
+        assert!(
+            fs::read_to_string(junit)
+                .unwrap()
+                .contains("name=\"v1.Service.default.a\"")
+        );
+        let sarif: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(sarif).unwrap()).unwrap();
+        assert_eq!(sarif["runs"][0]["results"][0]["ruleId"], "pruned");
+    }
+
+    #[test]
+    fn test_write_files_to_missing_directory_returns_error() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let junit = temp_dir.path().join("missing/junit.xml");
+        let report = Report::new(&[], &[], String::new(), None);
+        assert!(report.write_files(Some(&junit), None).is_err());
+    }
+}
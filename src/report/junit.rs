@@ -0,0 +1,108 @@
+use super::Report;
+use crate::template::ResourceContext;
+
+/// Render the report as JUnit XML with a failed test case per changed resource and a skipped one
+/// per ignored resource, so that the drift shows up in the test report of the MR/PR.
+pub fn render(report: &Report) -> String {
+    let suite = report.target.as_deref().map_or_else(
+        || "ksnotify".to_string(),
+        |target| format!("ksnotify ({target})"),
+    );
+    let tests = report.resources.len() + report.ignored.len();
+    let failures = report.resources.len();
+    let skipped = report.ignored.len();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"ksnotify\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">\n"
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{tests}\" failures=\"{failures}\" skipped=\"{skipped}\">\n",
+        escape(&suite)
+    ));
+    for resource in &report.resources {
+        xml.push_str(&testcase_open(resource));
+        xml.push_str(&format!(
+            "      <failure type=\"{}\" message=\"{} (+{}/-{})\">{}</failure>\n",
+            resource.action,
+            resource.action,
+            resource.added,
+            resource.removed,
+            escape(&resource.diff)
+        ));
+        xml.push_str("    </testcase>\n");
+    }
+    for resource in &report.ignored {
+        xml.push_str(&testcase_open(resource));
+        xml.push_str("      <skipped message=\"ignored\"/>\n");
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+    xml
+}
+
+fn testcase_open(resource: &ResourceContext) -> String {
+    format!(
+        "    <testcase classname=\"{}\" name=\"{}\">\n",
+        escape(&resource.namespace),
+        escape(&resource.id)
+    )
+}
+
+/// Escape a string for XML text and attributes. Control characters other than tab and newlines
+/// are not allowed in XML 1.0, so they are dropped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceChange;
+
+    #[test]
+    fn test_render_junit_with_changed_and_ignored_resources() {
+        let resources =
+            vec![ResourceChange::new("v1.ConfigMap.prod.conf", "-a: <1>\n+a: \"2\"").unwrap()];
+        let ignored = vec![ResourceChange::new("v1.Secret.prod.s", "+a").unwrap()];
+        let report = Report::new(
+            &resources,
+            &ignored,
+            String::new(),
+            Some("prod".to_string()),
+        );
+        let actual = render(&report);
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="ksnotify" tests="2" failures="1" skipped="1">
+  <testsuite name="ksnotify (prod)" tests="2" failures="1" skipped="1">
+    <testcase classname="prod" name="v1.ConfigMap.prod.conf">
+      <failure type="configured" message="configured (+1/-1)">-a: &lt;1&gt;
++a: &quot;2&quot;</failure>
+    </testcase>
+    <testcase classname="prod" name="v1.Secret.prod.s">
+      <skipped message="ignored"/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#;
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_escape_drops_control_characters() {
+        assert_eq!(escape("a\u{1b}[31m&b\n"), "a[31m&amp;b\n");
+    }
+}
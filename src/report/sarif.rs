@@ -0,0 +1,148 @@
+use super::Report;
+use crate::resource::Action;
+
+use serde_json::{Value, json};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const INFORMATION_URI: &str = "https://github.com/hirosassa/ksnotify";
+/// Base of the artifact URIs of the resources. `kubectl diff` does not tell which manifest file a
+/// resource comes from, so each resource is an artifact named by its id under this base.
+const URI_BASE_ID: &str = "RESOURCES";
+
+/// Render the report as SARIF 2.1.0 with a result per changed resource. The rule of a result is
+/// the action, and the resource is given as a logical location and as an artifact
+/// `%RESOURCES%/<id>`.
+pub fn render(report: &Report) -> Value {
+    let rules: Vec<Value> = [
+        Action::Created,
+        Action::Pruned,
+        Action::Replaced,
+        Action::Configured,
+    ]
+    .iter()
+    .map(|action| {
+        json!({
+            "id": action.to_string(),
+            "shortDescription": {"text": format!("The resource is {action}")},
+        })
+    })
+    .collect();
+
+    let results: Vec<Value> = report
+        .resources
+        .iter()
+        .map(|resource| {
+            let level = match resource.action {
+                Action::Pruned | Action::Replaced => "warning",
+                Action::Created | Action::Configured => "note",
+            };
+            json!({
+                "ruleId": resource.action.to_string(),
+                "level": level,
+                "message": {
+                    "text": format!(
+                        "{} is {} (+{}/-{})",
+                        resource.id, resource.action, resource.added, resource.removed
+                    ),
+                    "markdown": format!(
+                        "`{}` is {} (+{}/-{})\n\n```diff\n{}\n```",
+                        resource.id, resource.action, resource.added, resource.removed, resource.diff
+                    ),
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {"uri": resource.id, "uriBaseId": URI_BASE_ID},
+                    },
+                    "logicalLocations": [{
+                        "fullyQualifiedName": resource.id,
+                        "name": resource.name,
+                        "kind": "resource",
+                    }],
+                }],
+            })
+        })
+        .collect();
+
+    let mut run = json!({
+        "tool": {
+            "driver": {
+                "name": "ksnotify",
+                "informationUri": INFORMATION_URI,
+                "rules": rules,
+            },
+        },
+        "originalUriBaseIds": {
+            URI_BASE_ID: {
+                "uri": "ksnotify:/resources/",
+                "description": {"text": "Kubernetes resources by [group.]version.kind.namespace.name"},
+            },
+        },
+        "results": results,
+    });
+    if let Some(target) = &report.target {
+        run["automationDetails"] = json!({"id": format!("ksnotify/{target}/")});
+    }
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [run],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceChange;
+
+    #[test]
+    fn test_render_sarif_result_per_resource() {
+        let resources = vec![
+            ResourceChange::new("apps.v1.Deployment.prod.app", "-a\n+b").unwrap(),
+            ResourceChange::new("v1.Service.prod.web", "-a").unwrap(),
+        ];
+        let report = Report::new(&resources, &[], String::new(), Some("prod".to_string()));
+        let actual = render(&report);
+        assert_eq!(actual["version"], "2.1.0");
+        let run = &actual["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "ksnotify");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 4);
+        assert_eq!(run["automationDetails"]["id"], "ksnotify/prod/");
+        assert_eq!(
+            run["originalUriBaseIds"]["RESOURCES"]["uri"],
+            "ksnotify:/resources/"
+        );
+        assert_eq!(
+            run["results"][0],
+            json!({
+                "ruleId": "configured",
+                "level": "note",
+                "message": {
+                    "text": "apps.v1.Deployment.prod.app is configured (+1/-1)",
+                    "markdown": "`apps.v1.Deployment.prod.app` is configured (+1/-1)\n\n```diff\n-a\n+b\n```",
+                },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": {
+                            "uri": "apps.v1.Deployment.prod.app",
+                            "uriBaseId": "RESOURCES",
+                        },
+                    },
+                    "logicalLocations": [{
+                        "fullyQualifiedName": "apps.v1.Deployment.prod.app",
+                        "name": "app",
+                        "kind": "resource",
+                    }],
+                }],
+            })
+        );
+        assert_eq!(run["results"][1]["level"], "warning");
+    }
+
+    #[test]
+    fn test_render_sarif_without_changes() {
+        let report = Report::new(&[], &[], String::new(), None);
+        let actual = render(&report);
+        assert_eq!(actual["runs"][0]["results"], json!([]));
+        assert!(actual["runs"][0].get("automationDetails").is_none());
+    }
+}
@@ -0,0 +1,182 @@
+use super::Report;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const DIM: &str = "\x1b[2m";
+
+/// Render the report as plain text for a terminal, with ANSI colors if `color` is set.
+pub fn render(report: &Report, color: bool) -> String {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    };
+
+    let mut out = String::new();
+    for resource in &report.resources {
+        out.push_str(&format!(
+            "{} ({}, +{}/-{})\n",
+            paint(BOLD, &resource.id),
+            resource.action,
+            resource.added,
+            resource.removed
+        ));
+        if let Some(changes) = &resource.changes {
+            // path-based changes of the semantic diff mode instead of the line diff
+            if changes.is_empty() {
+                out.push_str(&paint(
+                    DIM,
+                    "No semantic changes (only ordering or formatting).",
+                ));
+                out.push('\n');
+            }
+            for change in changes {
+                out.push_str(&format!(
+                    "{}: {} -> {}\n",
+                    change.path,
+                    paint(RED, change.before.as_deref().unwrap_or("(none)")),
+                    paint(GREEN, change.after.as_deref().unwrap_or("(none)"))
+                ));
+            }
+            out.push('\n');
+            continue;
+        }
+        for line in resource.diff.lines() {
+            let style = match line.chars().next() {
+                Some('+') => GREEN,
+                Some('-') => RED,
+                _ if line.starts_with("@@") => CYAN,
+                _ => "",
+            };
+            if style.is_empty() {
+                out.push_str(line);
+            } else {
+                out.push_str(&paint(style, line));
+            }
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    for resource in &report.ignored {
+        out.push_str(&paint(
+            DIM,
+            &format!("{} ({}, ignored)", resource.id, resource.action),
+        ));
+        out.push('\n');
+    }
+    if !report.ignored.is_empty() {
+        out.push('\n');
+    }
+
+    let counts = &report.counts;
+    let summary = if counts.total == 0 {
+        "No changes. Kubernetes configurations are up-to-date.".to_string()
+    } else {
+        format!(
+            "{} resources: {} created, {} pruned, {} replaced, {} configured (+{}/-{})",
+            counts.total,
+            counts.created,
+            counts.pruned,
+            counts.replaced,
+            counts.configured,
+            report.stats.added,
+            report.stats.removed
+        )
+    };
+    let summary = if counts.ignored == 0 {
+        summary
+    } else {
+        format!("{summary}, {} ignored", counts.ignored)
+    };
+    out.push_str(&paint(BOLD, &summary));
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceChange;
+    use crate::semantic::PathChange;
+
+    #[test]
+    fn test_render_plain_text() {
+        let resources = vec![
+            ResourceChange::new("v1.Service.default.a", "@@ -1,2 +1,2 @@\n x\n-a\n+b").unwrap(),
+        ];
+        let ignored = vec![ResourceChange::new("v1.Secret.default.s", "+a").unwrap()];
+        let report = Report::new(&resources, &ignored, String::new(), None);
+        let actual = render(&report, false);
+        let expected = "v1.Service.default.a (configured, +1/-1)
+@@ -1,2 +1,2 @@
+ x
+-a
++b
+
+v1.Secret.default.s (created, ignored)
+
+1 resources: 0 created, 0 pruned, 0 replaced, 1 configured (+1/-1), 1 ignored
+";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_render_with_colors() {
+        let resources = vec![ResourceChange::new("v1.Service.default.a", " x\n-a\n+b").unwrap()];
+        let report = Report::new(&resources, &[], String::new(), None);
+        let actual = render(&report, true);
+        assert!(actual.starts_with("\x1b[1mv1.Service.default.a\x1b[0m (configured, +1/-1)\n"));
+        assert!(actual.contains("\n x\n\x1b[31m-a\x1b[0m\n\x1b[32m+b\x1b[0m\n"));
+    }
+
+    #[test]
+    fn test_render_semantic_changes() {
+        let mut change = ResourceChange::new("apps.v1.Deployment.default.app", "-a\n+b").unwrap();
+        change.changes = Some(vec![
+            PathChange {
+                path: "spec.template.spec.containers[name=app].image".to_string(),
+                before: Some("app:1".to_string()),
+                after: Some("app:2".to_string()),
+            },
+            PathChange {
+                path: "metadata.labels.tier".to_string(),
+                before: None,
+                after: Some("web".to_string()),
+            },
+        ]);
+        let mut unchanged = ResourceChange::new("v1.Service.default.svc", "-a\n+a").unwrap();
+        unchanged.changes = Some(Vec::new());
+        let report = Report::new(&[change, unchanged], &[], String::new(), None);
+        let actual = render(&report, false);
+        let expected = "apps.v1.Deployment.default.app (configured, +1/-1)
+spec.template.spec.containers[name=app].image: app:1 -> app:2
+metadata.labels.tier: (none) -> web
+
+v1.Service.default.svc (configured, +1/-1)
+No semantic changes (only ordering or formatting).
+
+2 resources: 0 created, 0 pruned, 0 replaced, 2 configured (+2/-2)
+";
+        assert_eq!(actual, expected);
+
+        let actual = render(&report, true);
+        assert!(
+            actual
+                .contains("\nmetadata.labels.tier: \x1b[31m(none)\x1b[0m -> \x1b[32mweb\x1b[0m\n")
+        );
+    }
+
+    #[test]
+    fn test_render_without_changes() {
+        let report = Report::new(&[], &[], String::new(), None);
+        assert_eq!(
+            render(&report, false),
+            "No changes. Kubernetes configurations are up-to-date.\n"
+        );
+    }
+}
@@ -0,0 +1,638 @@
+use crate::semantic::PathChange;
+
+use anyhow::{Result, anyhow, bail};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::sync::LazyLock;
+use strum_macros::Display;
+
+// matches hunk header like "@@ -5,7 +5,6 @@" where the line counts are optional
+static HUNK_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^@@ -(?P<old_start>\d+)(?:,(?P<old_lines>\d+))? \+(?P<new_start>\d+)(?:,(?P<new_lines>\d+))? @@")
+        .unwrap()
+});
+
+/// What applying the manifests does to a resource.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Serialize, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Action {
+    /// The resource does not exist on the cluster yet.
+    Created,
+
+    /// The resource exists on the cluster and will be deleted.
+    Pruned,
+
+    /// The whole resource is removed and added again, so no line of it is kept.
+    Replaced,
+
+    /// The resource exists on the cluster and will be updated.
+    Configured,
+}
+
+impl Action {
+    /// Classify a file diff. `old_missing`/`new_missing` tell if a side of the diff is `/dev/null`.
+    pub fn classify(old_missing: bool, new_missing: bool, hunks: &[Hunk]) -> Self {
+        if old_missing || (!hunks.is_empty() && hunks.iter().all(Hunk::is_old_empty)) {
+            return Self::Created;
+        }
+        if new_missing || (!hunks.is_empty() && hunks.iter().all(Hunk::is_new_empty)) {
+            return Self::Pruned;
+        }
+        if let [hunk] = hunks
+            && hunk.is_whole_document_replaced()
+        {
+            return Self::Replaced;
+        }
+        Self::Configured
+    }
+}
+
+/// Number of added and removed lines in a diff.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Serialize)]
+pub struct LineStats {
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl LineStats {
+    fn count<'a>(lines: impl IntoIterator<Item = &'a String>) -> Self {
+        lines.into_iter().fold(Self::default(), |mut stats, line| {
+            if line.starts_with('+') {
+                stats.added += 1;
+            } else if line.starts_with('-') {
+                stats.removed += 1;
+            }
+            stats
+        })
+    }
+}
+
+impl std::iter::Sum for LineStats {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |total, stats| Self {
+            added: total.added + stats.added,
+            removed: total.removed + stats.removed,
+        })
+    }
+}
+
+/// A chunk of a diff introduced by an `@@ ... @@` line.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct Hunk {
+    /// The `@@ ... @@` line. Empty when the diff body does not start with a hunk header.
+    pub header: String,
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<String>,
+}
+
+impl Hunk {
+    /// Parse a hunk header. Returns `None` if `header` is not a valid `@@ -a,b +c,d @@` line.
+    pub fn parse_header(header: &str) -> Option<Self> {
+        let caps = HUNK_HEADER.captures(header)?;
+        let number = |name: &str| -> Option<usize> {
+            caps.name(name)
+                .map_or(Some(1), |m| m.as_str().parse::<usize>().ok())
+        };
+        Some(Self {
+            header: header.to_string(),
+            old_start: number("old_start")?,
+            old_lines: number("old_lines")?,
+            new_start: number("new_start")?,
+            new_lines: number("new_lines")?,
+            lines: Vec::new(),
+        })
+    }
+
+    /// Whether the old side of the hunk is empty, as in `@@ -0,0 +1,20 @@` produced by `diff -N`.
+    /// Without a header, the hunk must consist of added lines only.
+    fn is_old_empty(&self) -> bool {
+        if self.header.is_empty() {
+            return self.lines.iter().all(|l| l.starts_with('+'));
+        }
+        self.old_start == 0 && self.old_lines == 0
+    }
+
+    /// Whether the new side of the hunk is empty, as in `@@ -1,20 +0,0 @@` produced by `diff -N`.
+    /// Without a header, the hunk must consist of removed lines only.
+    fn is_new_empty(&self) -> bool {
+        if self.header.is_empty() {
+            return self.lines.iter().all(|l| l.starts_with('-'));
+        }
+        self.new_start == 0 && self.new_lines == 0
+    }
+
+    /// Whether the hunk removes the whole old document and adds a whole new one without any
+    /// common line. Needs the hunk header to tell that the hunk starts at the top of both files.
+    fn is_whole_document_replaced(&self) -> bool {
+        !self.header.is_empty()
+            && self.old_start == 1
+            && self.new_start == 1
+            && self.old_lines > 0
+            && self.new_lines > 0
+            && self.lines.iter().all(|l| l.starts_with(['+', '-', '\\']))
+    }
+
+    const fn headless() -> Self {
+        Self {
+            header: String::new(),
+            old_start: 0,
+            old_lines: 0,
+            new_start: 0,
+            new_lines: 0,
+            lines: Vec::new(),
+        }
+    }
+}
+
+/// A single Kubernetes resource that appears in the `kubectl diff` output.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct ResourceChange {
+    /// API group. Empty for the core group.
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    /// Empty for cluster-scoped resources.
+    pub namespace: String,
+    pub name: String,
+    pub action: Action,
+    pub hunks: Vec<Hunk>,
+    pub stats: LineStats,
+    /// Path-based changes of a configured resource. Set only in the semantic diff mode and when
+    /// the documents can be reconstructed from the hunks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Vec<PathChange>>,
+}
+
+impl ResourceChange {
+    /// Build a resource from the file name `kubectl diff` writes it to and its diff body.
+    /// The file name is `[group.]version.kind.namespace.name`, e.g. `apps.v1.Deployment.default.app`.
+    #[cfg(test)]
+    pub fn new(id: &str, diff: &str) -> Result<Self> {
+        let hunks = Self::split_hunks(diff);
+        Self::from_hunks(id, Action::classify(false, false, &hunks), hunks)
+    }
+
+    /// Build a resource from the file name `kubectl diff` writes it to and its parsed hunks.
+    pub fn from_hunks(id: &str, action: Action, hunks: Vec<Hunk>) -> Result<Self> {
+        let (group, version, kind, namespace, name) = Self::parse_id(id)?;
+        let stats = LineStats::count(hunks.iter().flat_map(|h| &h.lines));
+        Ok(Self {
+            group,
+            version,
+            kind,
+            namespace,
+            name,
+            action,
+            hunks,
+            stats,
+            changes: None,
+        })
+    }
+
+    /// Identifier in the same format as the `kubectl diff` file name.
+    pub fn id(&self) -> String {
+        let group = if self.group.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", self.group)
+        };
+        format!(
+            "{group}{}.{}.{}.{}",
+            self.version, self.kind, self.namespace, self.name
+        )
+    }
+
+    /// Diff body as text, hunk headers included.
+    pub fn diff(&self) -> String {
+        self.hunks
+            .iter()
+            .flat_map(|h| {
+                (!h.header.is_empty())
+                    .then_some(&h.header)
+                    .into_iter()
+                    .chain(&h.lines)
+            })
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Rewrite the diff body with `f` and recompute the hunks and line stats.
+    /// The action is kept as it is since it describes the resource, not the remaining lines.
+    pub fn map_diff(self, f: impl Fn(&str) -> String) -> Self {
+        let hunks = Self::split_hunks(&f(&self.diff()));
+        let stats = LineStats::count(hunks.iter().flat_map(|h| &h.lines));
+        Self {
+            hunks,
+            stats,
+            ..self
+        }
+    }
+
+    fn parse_id(id: &str) -> Result<(String, String, String, String, String)> {
+        let segments: Vec<&str> = id.split('.').collect();
+        // kind is the first segment in UpperCamelCase, everything before it is group and version
+        let kind_index = segments
+            .iter()
+            .position(|s| s.starts_with(|c: char| c.is_ascii_uppercase()))
+            .filter(|&i| i >= 1 && segments.len() >= i + 3)
+            .ok_or_else(|| anyhow!("unexpected resource name: {id}"))?;
+        Ok((
+            segments[..kind_index - 1].join("."),
+            segments[kind_index - 1].to_string(),
+            segments[kind_index].to_string(),
+            segments[kind_index + 1].to_string(),
+            segments[kind_index + 2..].join("."),
+        ))
+    }
+
+    fn split_hunks(diff: &str) -> Vec<Hunk> {
+        let mut hunks: Vec<Hunk> = Vec::new();
+        for line in diff.lines() {
+            if let Some(hunk) = Hunk::parse_header(line) {
+                hunks.push(hunk);
+                continue;
+            }
+            if hunks.is_empty() {
+                hunks.push(Hunk::headless());
+            }
+            if let Some(hunk) = hunks.last_mut() {
+                hunk.lines.push(line.to_string());
+            }
+        }
+        hunks
+    }
+}
+
+/// Selects resources by kind, namespace and name. A field that is not set matches any resource.
+/// Fields are glob patterns where `*` matches any string and `?` matches any character.
+#[derive(Debug, PartialEq, Eq, Clone, Default, Serialize, Deserialize)]
+pub struct ResourceSelector {
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub namespace: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+impl ResourceSelector {
+    pub fn matches(&self, change: &ResourceChange) -> bool {
+        let matches = |pattern: &Option<String>, actual: &str| {
+            pattern.as_deref().is_none_or(|p| glob_match(p, actual))
+        };
+        matches(&self.kind, &change.kind)
+            && matches(&self.namespace, &change.namespace)
+            && matches(&self.name, &change.name)
+    }
+
+    /// Split resources into the ones to report and the ignored ones. A resource is reported if it
+    /// matches any of `include` (or `include` is empty) and none of `exclude`.
+    pub fn filter(
+        resources: Vec<ResourceChange>,
+        include: &[Self],
+        exclude: &[Self],
+    ) -> (Vec<ResourceChange>, Vec<ResourceChange>) {
+        resources.into_iter().partition(|change| {
+            (include.is_empty() || include.iter().any(|s| s.matches(change)))
+                && !exclude.iter().any(|s| s.matches(change))
+        })
+    }
+}
+
+impl FromStr for ResourceSelector {
+    type Err = anyhow::Error;
+
+    /// Parse comma separated conditions like `kind=Deployment,name=*-canary`.
+    fn from_str(s: &str) -> Result<Self> {
+        let mut selector = Self::default();
+        for condition in s.split(',') {
+            let (key, value) = condition
+                .split_once('=')
+                .ok_or_else(|| anyhow!("invalid selector {s:?}: expected key=value"))?;
+            let field = match key.trim() {
+                "kind" => &mut selector.kind,
+                "namespace" => &mut selector.namespace,
+                "name" => &mut selector.name,
+                _ => bail!("invalid selector {s:?}: unknown key {key:?}"),
+            };
+            *field = Some(value.trim().to_string());
+        }
+        Ok(selector)
+    }
+}
+
+/// Match `text` against a glob `pattern` where `*` matches any string and `?` matches any character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it is matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_parses_namespaced_resource_with_group() {
+        let actual = ResourceChange::new("apps.v1.Deployment.default.app", "").unwrap();
+        assert_eq!(actual.group, "apps");
+        assert_eq!(actual.version, "v1");
+        assert_eq!(actual.kind, "Deployment");
+        assert_eq!(actual.namespace, "default");
+        assert_eq!(actual.name, "app");
+    }
+
+    #[test]
+    fn test_new_parses_core_resource() {
+        let actual = ResourceChange::new("v1.Service.test.test-app", "").unwrap();
+        assert_eq!(actual.group, "");
+        assert_eq!(actual.version, "v1");
+        assert_eq!(actual.kind, "Service");
+    }
+
+    #[test]
+    fn test_new_parses_dotted_group_and_name() {
+        let actual =
+            ResourceChange::new("networking.k8s.io.v1.Ingress.web.www.example.com", "").unwrap();
+        assert_eq!(actual.group, "networking.k8s.io");
+        assert_eq!(actual.version, "v1");
+        assert_eq!(actual.kind, "Ingress");
+        assert_eq!(actual.namespace, "web");
+        assert_eq!(actual.name, "www.example.com");
+    }
+
+    #[test]
+    fn test_new_parses_cluster_scoped_resource() {
+        let actual = ResourceChange::new("v1.Namespace..test", "").unwrap();
+        assert_eq!(actual.kind, "Namespace");
+        assert_eq!(actual.namespace, "");
+        assert_eq!(actual.name, "test");
+        assert_eq!(actual.id(), "v1.Namespace..test");
+    }
+
+    #[test]
+    fn test_new_with_invalid_name_returns_error() {
+        assert!(ResourceChange::new("kind-a", "").is_err());
+        assert!(ResourceChange::new("v1.Service.test", "").is_err());
+    }
+
+    #[test]
+    fn test_id_round_trips() {
+        let id = "apps.v1.Deployment.default.app";
+        let actual = ResourceChange::new(id, "").unwrap();
+        assert_eq!(actual.id(), id);
+    }
+
+    #[test]
+    fn test_new_splits_hunks_and_counts_lines() {
+        let diff = "@@ -1,3 +1,3 @@
+ metadata:
+-  name: a
++  name: b
+@@ -10,2 +10,3 @@
+ spec:
++  replicas: 2";
+        let actual = ResourceChange::new("v1.Service.test.app", diff).unwrap();
+        assert_eq!(actual.hunks.len(), 2);
+        assert_eq!(actual.hunks[0].header, "@@ -1,3 +1,3 @@");
+        assert_eq!(actual.hunks[1].lines, vec![" spec:", "+  replicas: 2"]);
+        assert_eq!(
+            actual.stats,
+            LineStats {
+                added: 2,
+                removed: 1
+            }
+        );
+        assert_eq!(actual.diff(), diff);
+    }
+
+    #[test]
+    fn test_parse_header_reads_ranges() {
+        let actual = Hunk::parse_header("@@ -5,7 +5,6 @@ metadata:").unwrap();
+        assert_eq!(actual.header, "@@ -5,7 +5,6 @@ metadata:");
+        assert_eq!(
+            (
+                actual.old_start,
+                actual.old_lines,
+                actual.new_start,
+                actual.new_lines
+            ),
+            (5, 7, 5, 6)
+        );
+    }
+
+    #[test]
+    fn test_parse_header_defaults_line_counts_to_one() {
+        let actual = Hunk::parse_header("@@ -3 +4 @@").unwrap();
+        assert_eq!((actual.old_lines, actual.new_lines), (1, 1));
+    }
+
+    #[test]
+    fn test_parse_header_rejects_other_lines() {
+        assert!(Hunk::parse_header("@@ broken @@").is_none());
+        assert!(Hunk::parse_header(" @@ -1 +1 @@").is_none());
+    }
+
+    #[test]
+    fn test_new_keeps_lines_without_hunk_header() {
+        let diff = "- ABCDE\n+ FGHIJ";
+        let actual = ResourceChange::new("v1.Service.test.app", diff).unwrap();
+        assert_eq!(actual.hunks.len(), 1);
+        assert_eq!(actual.hunks[0].header, "");
+        assert_eq!(actual.diff(), diff);
+    }
+
+    #[test]
+    fn test_new_detects_action() {
+        let created = ResourceChange::new("v1.Service.test.app", "+kind: Service").unwrap();
+        let pruned = ResourceChange::new("v1.Service.test.app", "-kind: Service").unwrap();
+        let configured =
+            ResourceChange::new("v1.Service.test.app", "-  port: 80\n+  port: 81").unwrap();
+        assert_eq!(created.action, Action::Created);
+        assert_eq!(pruned.action, Action::Pruned);
+        assert_eq!(configured.action, Action::Configured);
+    }
+
+    #[test]
+    fn test_classify_created_by_empty_old_range() {
+        let hunks = ResourceChange::split_hunks("@@ -0,0 +1,2 @@\n+kind: Service\n+metadata:");
+        assert_eq!(Action::classify(false, false, &hunks), Action::Created);
+    }
+
+    #[test]
+    fn test_classify_pruned_by_empty_new_range() {
+        let hunks = ResourceChange::split_hunks("@@ -1,2 +0,0 @@\n-kind: Service\n-metadata:");
+        assert_eq!(Action::classify(false, false, &hunks), Action::Pruned);
+    }
+
+    #[test]
+    fn test_classify_by_dev_null_sides() {
+        let hunks = ResourceChange::split_hunks("@@ -1 +1 @@\n-a\n+b");
+        assert_eq!(Action::classify(true, false, &hunks), Action::Created);
+        assert_eq!(Action::classify(false, true, &hunks), Action::Pruned);
+    }
+
+    #[test]
+    fn test_classify_ignores_embedded_kind_lines() {
+        // a ConfigMap carrying a manifest must not be taken as a created resource
+        let hunks = ResourceChange::split_hunks(
+            "@@ -3,4 +3,5 @@
+ data:
+   manifest.yaml: |
+     apiVersion: v1
++    kind: Service
+     metadata:",
+        );
+        assert_eq!(Action::classify(false, false, &hunks), Action::Configured);
+    }
+
+    #[test]
+    fn test_classify_replaced_when_no_line_is_kept() {
+        let hunks = ResourceChange::split_hunks(
+            "@@ -1,2 +1,2 @@
+-apiVersion: v1
+-kind: Service
++apiVersion: apps/v1
++kind: Deployment",
+        );
+        assert_eq!(Action::classify(false, false, &hunks), Action::Replaced);
+    }
+
+    #[test]
+    fn test_classify_configured_when_hunk_does_not_start_at_top() {
+        let hunks = ResourceChange::split_hunks("@@ -5 +5 @@\n-  port: 80\n+  port: 81");
+        assert_eq!(Action::classify(false, false, &hunks), Action::Configured);
+    }
+
+    #[test]
+    fn test_map_diff_recomputes_stats_and_keeps_action() {
+        let change =
+            ResourceChange::new("v1.Service.test.app", "+kind: Service\n+  port: 80").unwrap();
+        let actual = change.map_diff(|d| d.replace("+kind: Service", " kind: Service"));
+        assert_eq!(actual.action, Action::Created);
+        assert_eq!(actual.stats.added, 1);
+    }
+
+    #[test]
+    fn test_line_stats_sum() {
+        let actual: LineStats = [
+            LineStats {
+                added: 1,
+                removed: 2,
+            },
+            LineStats {
+                added: 3,
+                removed: 0,
+            },
+        ]
+        .into_iter()
+        .sum();
+        assert_eq!(
+            actual,
+            LineStats {
+                added: 4,
+                removed: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_selector_matches_set_fields_only() {
+        let change = ResourceChange::new("apps.v1.Deployment.default.app", "").unwrap();
+        let selector =
+            |kind: Option<&str>, namespace: Option<&str>, name: Option<&str>| ResourceSelector {
+                kind: kind.map(String::from),
+                namespace: namespace.map(String::from),
+                name: name.map(String::from),
+            };
+        assert!(selector(None, None, None).matches(&change));
+        assert!(selector(Some("Deployment"), Some("default"), None).matches(&change));
+        assert!(!selector(Some("Deployment"), Some("kube-system"), None).matches(&change));
+        assert!(!selector(None, None, Some("other")).matches(&change));
+    }
+
+    #[test]
+    fn test_selector_matches_glob_patterns() {
+        let change = ResourceChange::new("apps.v1.Deployment.prod.web-canary", "").unwrap();
+        let selector = ResourceSelector::from_str("kind=Deploy*,name=*-canary").unwrap();
+        assert!(selector.matches(&change));
+        let selector = ResourceSelector::from_str("namespace=pro?").unwrap();
+        assert!(selector.matches(&change));
+        let selector = ResourceSelector::from_str("name=*-stable").unwrap();
+        assert!(!selector.matches(&change));
+    }
+
+    #[test]
+    fn test_selector_from_str() {
+        assert_eq!(
+            ResourceSelector::from_str("kind=Secret, namespace=kube-system").unwrap(),
+            ResourceSelector {
+                kind: Some("Secret".to_string()),
+                namespace: Some("kube-system".to_string()),
+                name: None,
+            }
+        );
+        assert!(ResourceSelector::from_str("Secret").is_err());
+        assert!(ResourceSelector::from_str("group=apps").is_err());
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYbZc"));
+        assert!(glob_match("a?c", "abc"));
+        assert!(!glob_match("a?c", "ac"));
+        assert!(!glob_match("a*b", "aXc"));
+        assert!(glob_match("*-canary", "web-canary"));
+    }
+
+    #[test]
+    fn test_filter_splits_reported_and_ignored_resources() {
+        let resources = vec![
+            ResourceChange::new("v1.Secret.default.token", "").unwrap(),
+            ResourceChange::new("v1.Service.kube-system.dns", "").unwrap(),
+            ResourceChange::new("v1.Service.default.web", "").unwrap(),
+        ];
+        let include = [ResourceSelector::from_str("kind=Service").unwrap()];
+        let exclude = [ResourceSelector::from_str("namespace=kube-system").unwrap()];
+        let (reported, ignored) = ResourceSelector::filter(resources, &include, &exclude);
+        let ids = |r: &[ResourceChange]| r.iter().map(ResourceChange::id).collect::<Vec<_>>();
+        assert_eq!(ids(&reported), vec!["v1.Service.default.web"]);
+        assert_eq!(
+            ids(&ignored),
+            vec!["v1.Secret.default.token", "v1.Service.kube-system.dns"]
+        );
+    }
+
+    #[test]
+    fn test_filter_without_selectors_reports_everything() {
+        let resources = vec![ResourceChange::new("v1.Secret.default.token", "").unwrap()];
+        let (reported, ignored) = ResourceSelector::filter(resources, &[], &[]);
+        assert_eq!(reported.len(), 1);
+        assert!(ignored.is_empty());
+    }
+}
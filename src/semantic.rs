@@ -0,0 +1,353 @@
+use crate::resource::Hunk;
+
+use anyhow::Result;
+use serde::Serialize;
+use serde_yml::Value;
+
+/// A change of a single value in a YAML document, e.g.
+/// `spec.template.spec.containers[name=app].image: a -> b`.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct PathChange {
+    pub path: String,
+    /// `None` if the path is added.
+    pub before: Option<String>,
+    /// `None` if the path is removed.
+    pub after: Option<String>,
+}
+
+/// Number of context lines of `diff -u`, the default of `kubectl diff`.
+const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Compute path-based changes from the hunks of a resource.
+/// Returns `None` if the diff does not consist of a single hunk starting at the top of the
+/// document. The hunk is taken as the whole document, so check `is_whole_document` on the hunks
+/// as parsed, before suppression removes lines from them.
+pub fn changes(hunks: &[Hunk]) -> Option<Vec<PathChange>> {
+    let (before, after) = reconstruct(hunks)?;
+    diff_documents(&before, &after).ok()
+}
+
+/// Whether the hunks cover the whole document, i.e. a single hunk from the top to the end of both
+/// the old and the new document. Run `diff` with a large context (e.g.
+/// `KUBECTL_EXTERNAL_DIFF="diff -u -N -U 10000"`) to get such a hunk.
+pub fn is_whole_document(hunks: &[Hunk]) -> bool {
+    let Some((before, after)) = reconstruct(hunks) else {
+        return false;
+    };
+    let hunk = &hunks[0];
+    if before.lines().count() != hunk.old_lines || after.lines().count() != hunk.new_lines {
+        return false;
+    }
+    // The end of the document is not marked in a unified diff. A hunk cut before the end has
+    // exactly as many trailing context lines as `diff` was asked for, and as it starts at the
+    // top, no more leading context lines than that. So the hunk is taken as cut only if it looks
+    // like a hunk of `diff -u`.
+    let is_context = |line: &&String| line.starts_with(' ') || line.is_empty();
+    let leading = hunk.lines.iter().take_while(is_context).count();
+    let trailing = hunk.lines.iter().rev().take_while(is_context).count();
+    !(trailing == DEFAULT_CONTEXT_LINES && leading <= DEFAULT_CONTEXT_LINES)
+}
+
+/// Reconstruct the old and the new document from a hunk that starts at the top of both.
+fn reconstruct(hunks: &[Hunk]) -> Option<(String, String)> {
+    let [hunk] = hunks else {
+        return None;
+    };
+    if hunk.header.is_empty() || hunk.old_start > 1 || hunk.new_start > 1 {
+        return None;
+    }
+    let mut before = String::new();
+    let mut after = String::new();
+    for line in &hunk.lines {
+        let (body, old, new) = match line.chars().next() {
+            Some('-') => (&line[1..], true, false),
+            Some('+') => (&line[1..], false, true),
+            Some(' ') => (&line[1..], true, true),
+            None => ("", true, true),
+            _ => continue,
+        };
+        if old {
+            before.push_str(body);
+            before.push('\n');
+        }
+        if new {
+            after.push_str(body);
+            after.push('\n');
+        }
+    }
+    Some((before, after))
+}
+
+/// Compute path-based changes between two YAML documents.
+pub fn diff_documents(before: &str, after: &str) -> Result<Vec<PathChange>> {
+    let before: Value = serde_yml::from_str(before)?;
+    let after: Value = serde_yml::from_str(after)?;
+    let mut changes = Vec::new();
+    diff_values("", &before, &after, &mut changes);
+    Ok(changes)
+}
+
+fn diff_values(path: &str, before: &Value, after: &Value, changes: &mut Vec<PathChange>) {
+    match (before, after) {
+        (Value::Mapping(old), Value::Mapping(new)) => {
+            for (key, old_value) in old {
+                let path = join_key(path, key);
+                match new.get(key) {
+                    Some(new_value) => diff_values(&path, old_value, new_value, changes),
+                    None => changes.push(removed(path, old_value)),
+                }
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    changes.push(added(join_key(path, key), new_value));
+                }
+            }
+        }
+        (Value::Sequence(old), Value::Sequence(new)) => {
+            if let (Some(old_names), Some(new_names)) = (names(old), names(new)) {
+                // match list items by name as in containers, env or volumes
+                for (name, old_value) in old_names.iter().zip(old) {
+                    let path = format!("{path}[name={name}]");
+                    match new_names.iter().position(|n| n == name) {
+                        Some(i) => diff_values(&path, old_value, &new[i], changes),
+                        None => changes.push(removed(path, old_value)),
+                    }
+                }
+                for (name, new_value) in new_names.iter().zip(new) {
+                    if !old_names.contains(name) {
+                        changes.push(added(format!("{path}[name={name}]"), new_value));
+                    }
+                }
+                return;
+            }
+            for (i, old_value) in old.iter().enumerate() {
+                let path = format!("{path}[{i}]");
+                match new.get(i) {
+                    Some(new_value) => diff_values(&path, old_value, new_value, changes),
+                    None => changes.push(removed(path, old_value)),
+                }
+            }
+            for (i, new_value) in new.iter().enumerate().skip(old.len()) {
+                changes.push(added(format!("{path}[{i}]"), new_value));
+            }
+        }
+        _ if before == after => {}
+        (Value::Null, _) => changes.push(added(path.to_string(), after)),
+        (_, Value::Null) => changes.push(removed(path.to_string(), before)),
+        _ => changes.push(PathChange {
+            path: path.to_string(),
+            before: Some(render(before)),
+            after: Some(render(after)),
+        }),
+    }
+}
+
+/// Names of list items if all of them are mappings with a string `name` field.
+fn names(items: &[Value]) -> Option<Vec<&str>> {
+    items
+        .iter()
+        .map(|item| item.get("name").and_then(Value::as_str))
+        .collect()
+}
+
+fn join_key(path: &str, key: &str) -> String {
+    if key.contains(['.', '[', ']', ' ']) {
+        format!("{path}[\"{key}\"]")
+    } else if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{path}.{key}")
+    }
+}
+
+fn added(path: String, value: &Value) -> PathChange {
+    PathChange {
+        path,
+        before: None,
+        after: Some(render(value)),
+    }
+}
+
+fn removed(path: String, value: &Value) -> PathChange {
+    PathChange {
+        path,
+        before: Some(render(value)),
+        after: None,
+    }
+}
+
+/// Render a value in a single line. Mappings and sequences are rendered in JSON.
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(path: &str, before: Option<&str>, after: Option<&str>) -> PathChange {
+        PathChange {
+            path: path.to_string(),
+            before: before.map(String::from),
+            after: after.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_diff_documents_reports_changed_scalar() {
+        let actual = diff_documents("spec:\n  replicas: 1\n", "spec:\n  replicas: 2\n").unwrap();
+        assert_eq!(actual, vec![change("spec.replicas", Some("1"), Some("2"))]);
+    }
+
+    #[test]
+    fn test_diff_documents_ignores_key_order() {
+        let actual = diff_documents("a: 1\nb: 2\n", "b: 2\na: 1\n").unwrap();
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn test_diff_documents_matches_list_items_by_name() {
+        let before = "spec:
+  containers:
+  - name: sidecar
+    image: proxy:1
+  - name: app
+    image: a
+";
+        let after = "spec:
+  containers:
+  - name: app
+    image: b
+  - name: sidecar
+    image: proxy:1
+";
+        let actual = diff_documents(before, after).unwrap();
+        assert_eq!(
+            actual,
+            vec![change(
+                "spec.containers[name=app].image",
+                Some("a"),
+                Some("b")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_diff_documents_matches_other_lists_by_index() {
+        let actual = diff_documents("args:\n- a\n- b\n", "args:\n- a\n- c\n- d\n").unwrap();
+        assert_eq!(
+            actual,
+            vec![
+                change("args[1]", Some("b"), Some("c")),
+                change("args[2]", None, Some("d")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_documents_reports_added_and_removed_subtrees() {
+        let actual = diff_documents(
+            "metadata:\n  labels:\n    app: a\n",
+            "metadata:\n  annotations:\n    note: x\n",
+        )
+        .unwrap();
+        assert_eq!(
+            actual,
+            vec![
+                change("metadata.labels", Some(r#"{"app":"a"}"#), None),
+                change("metadata.annotations", None, Some(r#"{"note":"x"}"#)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_documents_quotes_keys_with_dots() {
+        let actual = diff_documents(
+            "metadata:\n  annotations:\n    example.com/owner: a\n",
+            "metadata:\n  annotations:\n    example.com/owner: b\n",
+        )
+        .unwrap();
+        assert_eq!(
+            actual,
+            vec![change(
+                r#"metadata.annotations["example.com/owner"]"#,
+                Some("a"),
+                Some("b")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_changes_reconstructs_documents_from_full_context_hunk() {
+        let hunk = Hunk {
+            lines: vec![
+                " spec:".to_string(),
+                "-  replicas: 1".to_string(),
+                "+  replicas: 2".to_string(),
+                "   paused: false".to_string(),
+            ],
+            ..Hunk::parse_header("@@ -1,3 +1,3 @@").unwrap()
+        };
+        let actual = changes(&[hunk]).unwrap();
+        assert_eq!(actual, vec![change("spec.replicas", Some("1"), Some("2"))]);
+    }
+
+    #[test]
+    fn test_changes_returns_none_for_partial_hunk() {
+        let hunk = Hunk {
+            lines: vec!["-  replicas: 1".to_string(), "+  replicas: 2".to_string()],
+            ..Hunk::parse_header("@@ -12 +12 @@").unwrap()
+        };
+        assert!(changes(&[hunk]).is_none());
+    }
+
+    #[test]
+    fn test_is_whole_document_with_full_context_hunk() {
+        let hunk = Hunk {
+            lines: vec![
+                " spec:".to_string(),
+                "-  replicas: 1".to_string(),
+                "+  replicas: 2".to_string(),
+                "   paused: false".to_string(),
+            ],
+            ..Hunk::parse_header("@@ -1,3 +1,3 @@").unwrap()
+        };
+        assert!(is_whole_document(&[hunk]));
+    }
+
+    #[test]
+    fn test_is_whole_document_rejects_partial_hunk_at_top() {
+        // `diff -u` of a longer document, cut 3 lines after the change
+        let hunk = Hunk {
+            lines: vec![
+                " metadata:".to_string(),
+                "-  name: a".to_string(),
+                "+  name: b".to_string(),
+                "   namespace: default".to_string(),
+                "   labels:".to_string(),
+                "     app: a".to_string(),
+            ],
+            ..Hunk::parse_header("@@ -1,5 +1,5 @@").unwrap()
+        };
+        assert!(changes(std::slice::from_ref(&hunk)).is_some());
+        assert!(!is_whole_document(&[hunk]));
+    }
+
+    #[test]
+    fn test_is_whole_document_rejects_hunk_with_missing_lines() {
+        let hunk = Hunk {
+            lines: vec!["-  replicas: 1".to_string(), "+  replicas: 2".to_string()],
+            ..Hunk::parse_header("@@ -1,3 +1,3 @@").unwrap()
+        };
+        assert!(!is_whole_document(&[hunk]));
+    }
+
+    #[test]
+    fn test_changes_returns_none_for_multiple_hunks() {
+        let hunk = Hunk::parse_header("@@ -1 +1 @@").unwrap();
+        assert!(changes(&[hunk.clone(), hunk]).is_none());
+    }
+}
@@ -1,9 +1,141 @@
-use std::collections::HashMap;
+mod helpers;
+mod slack;
 
-use anyhow::Result;
+use crate::resource::{Action, LineStats, ResourceChange};
+use crate::semantic::PathChange;
+
+use anyhow::{Context, Result};
 use handlebars::Handlebars;
 use itertools::Itertools;
-use serde::Serialize;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+// matches the page marker at the end of the first line of a page like " (part 2/3)"
+static PAGE_MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r" \(part (\d+)/(\d+)\)$").unwrap());
+
+/// Bytes reserved for the page marker when packing the details into pages.
+const PAGE_MARKER_RESERVE: usize = 32;
+
+/// Handlebars template given inline or by a file path, e.g. `title_template: "## {{target}}"` or
+/// `body_template: {file: body.hbs}` in the config file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TemplateSource {
+    Inline(String),
+    File { file: PathBuf },
+}
+
+impl TemplateSource {
+    pub fn load(&self) -> Result<String> {
+        match self {
+            Self::Inline(template) => Ok(template.clone()),
+            Self::File { file } => fs::read_to_string(file)
+                .with_context(|| format!("failed to read template: {}", file.display())),
+        }
+    }
+
+    /// Resolve a relative file path against `base_dir`, i.e. the directory of the config file.
+    pub fn relative_to(self, base_dir: &Path) -> Self {
+        match self {
+            Self::File { file } if file.is_relative() => Self::File {
+                file: base_dir.join(file),
+            },
+            other => other,
+        }
+    }
+}
+
+/// A resource in the template context, e.g. `{{#each resources}}{{id}}: +{{added}}{{/each}}`.
+/// The reports of the parse result share it, so that every output has the same fields.
+#[derive(Serialize, Debug)]
+pub struct ResourceContext {
+    pub id: String,
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub action: Action,
+    pub added: usize,
+    pub removed: usize,
+    pub diff: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Vec<PathChange>>,
+}
+
+impl From<&ResourceChange> for ResourceContext {
+    fn from(change: &ResourceChange) -> Self {
+        Self {
+            id: change.id(),
+            group: change.group.clone(),
+            version: change.version.clone(),
+            kind: change.kind.clone(),
+            namespace: change.namespace.clone(),
+            name: change.name.clone(),
+            action: change.action,
+            added: change.stats.added,
+            removed: change.stats.removed,
+            diff: change.diff(),
+            changes: change.changes.clone(),
+        }
+    }
+}
+
+/// Number of resources by action, e.g. `{{counts.created}}`.
+#[derive(Serialize, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Counts {
+    pub created: usize,
+    pub pruned: usize,
+    pub replaced: usize,
+    pub configured: usize,
+    pub ignored: usize,
+    pub total: usize,
+}
+
+impl Counts {
+    pub fn new(resources: &[ResourceChange], ignored: &[ResourceChange]) -> Self {
+        let count = |action: Action| resources.iter().filter(|r| r.action == action).count();
+        Self {
+            created: count(Action::Created),
+            pruned: count(Action::Pruned),
+            replaced: count(Action::Replaced),
+            configured: count(Action::Configured),
+            ignored: ignored.len(),
+            total: resources.len(),
+        }
+    }
+
+    pub const fn of(&self, action: Action) -> usize {
+        match action {
+            Action::Created => self.created,
+            Action::Pruned => self.pruned,
+            Action::Replaced => self.replaced,
+            Action::Configured => self.configured,
+        }
+    }
+}
+
+/// Resources of a namespace in the grouped layout. The namespace is empty for cluster-scoped ones.
+#[derive(Serialize, Debug)]
+struct NamespaceGroup {
+    namespace: String,
+    count: usize,
+    kinds: Vec<KindGroup>,
+}
+
+#[derive(Serialize, Debug)]
+struct KindGroup {
+    kind: String,
+    count: usize,
+    /// Names and actions of the resources like "app (configured)".
+    resources: Vec<String>,
+}
 
 #[derive(Serialize, Debug)]
 pub struct Template {
@@ -11,9 +143,28 @@ pub struct Template {
     configured_kinds: Vec<String>,
     created_kinds: Vec<String>,
     pruned_kinds: Vec<String>,
+    replaced_kinds: Vec<String>,
+    ignored_kinds: Vec<String>,
+    /// Resources grouped by namespace and kind. Empty unless the grouped layout is enabled.
+    groups: Vec<NamespaceGroup>,
+    resources: Vec<ResourceContext>,
+    counts: Counts,
+    /// Added and removed lines of all the resources, e.g. `+{{stats.added}}`.
+    stats: LineStats,
     details: String,
     link: String,
     is_no_changes: bool,
+    #[serde(skip)]
+    title_template: String,
+    #[serde(skip)]
+    body_template: String,
+    #[serde(skip)]
+    partials: BTreeMap<String, String>,
+    /// Details of each resource in the order of `resources`, to split the comment into pages.
+    #[serde(skip)]
+    sections: Vec<String>,
+    #[serde(skip)]
+    max_comment_size: Option<usize>,
 }
 
 impl Template {
@@ -27,6 +178,23 @@ impl Template {
 No changes. Kubernetes configurations are up-to-date.
 ```
 {{else}}
+{{#if (gt (len groups) 0)}}
+{{#each groups}}
+<details><summary>{{#if namespace}}{{namespace}}{{else}}(cluster){{/if}} ({{count}})</summary>
+
+{{#each kinds}}
+<details><summary>{{kind}} ({{count}})</summary>
+
+{{#each resources}}
+* {{this}}
+{{/each}}
+
+</details>
+{{/each}}
+
+</details>
+{{/each}}
+{{else}}
 {{#if (gt (len created_kinds) 0)}}
 ## created
 {{#each created_kinds}}
@@ -39,122 +207,533 @@ No changes. Kubernetes configurations are up-to-date.
 * {{this}}
 {{/each}}
 {{/if}}
+{{#if (gt (len replaced_kinds) 0)}}
+## replaced
+{{#each replaced_kinds}}
+* {{this}}
+{{/each}}
+{{/if}}
 {{#if (gt (len configured_kinds) 0)}}
 ## configured
 {{#each configured_kinds}}
 * {{this}}
 {{/each}}
 {{/if}}
+{{/if}}
+
+| resource | action | added | removed |
+| --- | --- | --- | --- |
+{{#each resources}}
+| {{id}} | {{action}} | +{{added}} | -{{removed}} |
+{{/each}}
+| **total** | | +{{stats.added}} | -{{stats.removed}} |
 
 <details><summary>Details (Click me)</summary>
 
 {{{ details }}}
 
+</details>
+{{/if}}
+{{#if (gt (len ignored_kinds) 0)}}
+
+<details><summary>ignored ({{len ignored_kinds}})</summary>
+
+{{#each ignored_kinds}}
+* {{this}}
+{{/each}}
+
 </details>
 {{/if}}
 ";
 
-    pub fn new(results: HashMap<String, String>, link: String, target: Option<String>) -> Self {
+    pub fn new(results: Vec<ResourceChange>, link: String, target: Option<String>) -> Self {
         let configured_kinds = Self::generate_configured_kinds_markdown(&results);
         let created_kinds = Self::generate_created_kinds_markdown(&results);
         let pruned_kinds = Self::generate_pruned_kinds_markdown(&results);
-        let details = Self::generate_details_markdown(&results);
+        let replaced_kinds = Self::generate_replaced_kinds_markdown(&results);
+        let sections = Self::generate_detail_sections(&results);
+        let details = sections.join("\n");
+        let resources = results
+            .iter()
+            .sorted_by_key(|e| e.id())
+            .map(ResourceContext::from)
+            .collect();
+        let counts = Counts::new(&results, &[]);
+        let stats = results.iter().map(|e| e.stats).sum();
         let is_no_changes = results.is_empty();
         Self {
             target,
             configured_kinds,
             created_kinds,
             pruned_kinds,
+            replaced_kinds,
+            ignored_kinds: Vec::new(),
+            groups: Vec::new(),
+            resources,
+            counts,
+            stats,
             details,
             link,
             is_no_changes,
+            title_template: Self::DEFAULT_BUILD_TITLE_TEMPLATE.to_string(),
+            body_template: Self::DEFAULT_BUILD_BODY_TEMPLATE.to_string(),
+            partials: BTreeMap::new(),
+            sections,
+            max_comment_size: None,
+        }
+    }
+
+    /// Replace the default title and body templates. The first line of the rendered title
+    /// identifies the build for `--patch`, so it should not depend on the diff.
+    pub fn with_templates(mut self, title: Option<String>, body: Option<String>) -> Self {
+        if let Some(title) = title {
+            self.title_template = title;
         }
+        if let Some(body) = body {
+            self.body_template = body;
+        }
+        self
+    }
+
+    /// List the resources filtered out by `--include`/`--exclude` in the collapsed ignored section.
+    pub fn with_ignored(mut self, ignored: &[ResourceChange]) -> Self {
+        self.ignored_kinds = ignored
+            .iter()
+            .map(|e| format!("{} ({})", e.id(), e.action))
+            .sorted()
+            .collect();
+        self.counts.ignored = ignored.len();
+        self
+    }
+
+    /// Register partials to be used as `{{> name}}` in both the title and the body templates.
+    pub fn with_partials(mut self, partials: BTreeMap<String, String>) -> Self {
+        self.partials = partials;
+        self
+    }
+
+    /// Cut off the diffs of resources over `max_lines_per_resource` lines, and then the biggest
+    /// diffs until the details fit in `max_total_bytes`. Smaller diffs are kept whole. Semantic
+    /// change tables are not truncated.
+    pub fn with_truncation(
+        mut self,
+        max_lines_per_resource: Option<usize>,
+        max_total_bytes: Option<usize>,
+    ) -> Self {
+        let mut keep: Vec<Option<usize>> = self
+            .resources
+            .iter()
+            .map(|r| {
+                max_lines_per_resource
+                    .filter(|&max| r.changes.is_none() && r.diff.lines().count() > max)
+            })
+            .collect();
+        let mut sections: Vec<String> = self
+            .resources
+            .iter()
+            .zip(&self.sections)
+            .zip(&keep)
+            .map(|((r, section), keep)| {
+                keep.map_or_else(|| section.clone(), |keep| self.truncated_section(r, keep))
+            })
+            .collect();
+
+        if let Some(budget) = max_total_bytes {
+            let sizes: Vec<usize> = sections.iter().map(String::len).collect();
+            let cap = Self::size_cap(&sizes, budget);
+            for (i, r) in self.resources.iter().enumerate() {
+                if sections[i].len() <= cap || r.changes.is_some() {
+                    continue;
+                }
+                let overhead = self.truncated_section(r, 0).len();
+                let mut size = overhead;
+                let lines = r
+                    .diff
+                    .lines()
+                    .take_while(|line| {
+                        size += line.len() + 1;
+                        size <= cap
+                    })
+                    .count();
+                let lines = keep[i].map_or(lines, |k| k.min(lines));
+                keep[i] = Some(lines);
+                sections[i] = self.truncated_section(r, lines);
+            }
+        }
+
+        self.details = sections.join("\n");
+        self.sections = sections;
+        self
+    }
+
+    /// The largest section size such that the sections cut to it fit in `budget` bytes, so that
+    /// the sections smaller than it are kept whole.
+    fn size_cap(sizes: &[usize], budget: usize) -> usize {
+        let mut remaining = budget;
+        let mut count = sizes.len();
+        for &size in sizes.iter().sorted() {
+            if size * count <= remaining {
+                remaining -= size;
+                count -= 1;
+            } else {
+                return remaining / count;
+            }
+        }
+        usize::MAX
+    }
+
+    /// Section with the first `keep` lines of the diff and a footer about the elided lines.
+    fn truncated_section(&self, resource: &ResourceContext, keep: usize) -> String {
+        let lines: Vec<&str> = resource.diff.lines().collect();
+        let (kept, elided) = lines.split_at(keep.min(lines.len()));
+        let added = elided.iter().filter(|l| l.starts_with('+')).count();
+        let removed = elided.iter().filter(|l| l.starts_with('-')).count();
+        let job_log = if self.link.is_empty() {
+            "job log".to_string()
+        } else {
+            format!("[job log]({})", self.link)
+        };
+        format!(
+            "### {}\n```diff\n{}\n```\n… {} more lines (+{added}/-{removed}) truncated, see {job_log}",
+            resource.id,
+            kept.join("\n"),
+            elided.len(),
+        )
+    }
+
+    /// Nest the resources by namespace and then by kind in collapsible sections instead of the
+    /// flat lists by action.
+    pub fn with_grouped_layout(mut self, grouped: bool) -> Self {
+        if !grouped {
+            return self;
+        }
+        self.groups = self
+            .resources
+            .iter()
+            .into_group_map_by(|r| r.namespace.clone())
+            .into_iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .map(|(namespace, resources)| {
+                let kinds: Vec<KindGroup> = resources
+                    .into_iter()
+                    .into_group_map_by(|r| r.kind.clone())
+                    .into_iter()
+                    .sorted_by(|(a, _), (b, _)| a.cmp(b))
+                    .map(|(kind, resources)| KindGroup {
+                        kind,
+                        count: resources.len(),
+                        resources: resources
+                            .iter()
+                            .map(|r| format!("{} ({})", r.name, r.action))
+                            .collect(),
+                    })
+                    .collect();
+                NamespaceGroup {
+                    namespace,
+                    count: kinds.iter().map(|k| k.count).sum(),
+                    kinds,
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Limit the size of a comment in bytes in addition to the limit of the platform.
+    pub const fn with_max_comment_size(mut self, max_comment_size: Option<usize>) -> Self {
+        self.max_comment_size = max_comment_size;
+        self
+    }
+
+    /// Handlebars registry with the ksnotify helpers and the partials.
+    fn registry(&self) -> Result<Handlebars<'_>> {
+        let mut reg = Handlebars::new();
+        helpers::register(&mut reg);
+        for (name, partial) in &self.partials {
+            reg.register_partial(name, partial)
+                .with_context(|| format!("invalid partial: {name}"))?;
+        }
+        Ok(reg)
     }
 
     pub fn render(&self) -> Result<String> {
-        let reg = Handlebars::new();
+        let reg = self.registry()?;
         let j = serde_json::to_value(self)?;
-        let title = reg.render_template(Self::DEFAULT_BUILD_TITLE_TEMPLATE, &j)?;
-        let body = reg.render_template(Self::DEFAULT_BUILD_BODY_TEMPLATE, &j)?;
+        let title = reg.render_template(&self.title_template, &j)?;
+        let body = reg.render_template(&self.body_template, &j)?;
         Ok(format!("{title}{body}"))
     }
 
+    /// Render a Block Kit payload of a Slack incoming webhook. Only the first line of the title is
+    /// used, and the diffs are cut off as Slack has no collapsible sections.
+    pub fn render_slack(&self) -> Result<serde_json::Value> {
+        let reg = self.registry()?;
+        let j = serde_json::to_value(self)?;
+        let title = reg.render_template(&self.title_template, &j)?;
+        Ok(slack::render(
+            self,
+            title.lines().next().unwrap_or_default(),
+        ))
+    }
+
+    /// Render a JSON payload of a webhook from a Handlebars `template`. In addition to the context
+    /// of the comment, the rendered comment is given as `comment` and the first line of the title as
+    /// `title`. Nothing is HTML-escaped, so strings should be quoted by the `json` helper.
+    pub fn render_payload(&self, template: &str) -> Result<serde_json::Value> {
+        let mut reg = self.registry()?;
+        reg.register_escape_fn(handlebars::no_escape);
+        let mut j = serde_json::to_value(self)?;
+        let title = reg.render_template(&self.title_template, &j)?;
+        j["title"] = title.lines().next().unwrap_or_default().into();
+        j["comment"] = self.render()?.into();
+        let payload = reg
+            .render_template(template, &j)
+            .with_context(|| "failed to render webhook template")?;
+        serde_json::from_str(&payload)
+            .with_context(|| format!("webhook template must render JSON: {payload}"))
+    }
+
+    /// Render the comment as pages of at most `limit` bytes (or `max_comment_size` if smaller).
+    /// The details are split at resource boundaries, and the first line of the title of each page
+    /// gets a "(part i/n)" marker. The lists of resources are only on the first page.
+    pub fn render_pages(&self, limit: Option<usize>) -> Result<Vec<String>> {
+        let whole = self.render()?;
+        let limit = match (limit, self.max_comment_size) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(limit), None) | (None, Some(limit)) => limit,
+            (None, None) => return Ok(vec![whole]),
+        };
+        if whole.len() <= limit || self.sections.len() < 2 {
+            return Ok(vec![Self::fit(whole, limit)]);
+        }
+
+        let reg = self.registry()?;
+        // size of the first and the other pages without resources
+        let first_overhead = self.render_page(&reg, 0..0, 1, 1)?.len() + PAGE_MARKER_RESERVE;
+        let other_overhead = self.render_page(&reg, 0..0, 2, 2)?.len() + PAGE_MARKER_RESERVE;
+        // size a resource adds to a page: its details and whatever the body renders per resource,
+        // e.g. a row of the summary table, and a newline joining the details
+        let empty = other_overhead - PAGE_MARKER_RESERVE;
+        let costs = (0..self.sections.len())
+            .map(|i| {
+                Ok(self
+                    .render_page(&reg, i..i + 1, 2, 2)?
+                    .len()
+                    .saturating_sub(empty)
+                    + 1)
+            })
+            .collect::<Result<Vec<usize>>>()?;
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        let mut size = first_overhead;
+        for (i, cost) in costs.into_iter().enumerate() {
+            if i > start && size + cost > limit {
+                ranges.push(start..i);
+                start = i;
+                size = other_overhead;
+            }
+            size += cost;
+        }
+        ranges.push(start..self.sections.len());
+
+        // the estimate may be off with custom templates, so split the pages still too large
+        loop {
+            let pages = ranges.len();
+            let rendered = ranges
+                .iter()
+                .enumerate()
+                .map(|(i, range)| self.render_page(&reg, range.clone(), i + 1, pages))
+                .collect::<Result<Vec<String>>>()?;
+            let oversized = rendered
+                .iter()
+                .zip(&ranges)
+                .position(|(page, range)| page.len() > limit && range.len() > 1);
+            let Some(i) = oversized else {
+                return Ok(rendered
+                    .into_iter()
+                    .map(|page| Self::fit(page, limit))
+                    .collect());
+            };
+            let range = ranges.remove(i);
+            let middle = range.start + range.len() / 2;
+            ranges.insert(i, middle..range.end);
+            ranges.insert(i, range.start..middle);
+        }
+    }
+
+    /// Render a page with the details of the resources in `range`.
+    fn render_page(
+        &self,
+        reg: &Handlebars<'_>,
+        range: Range<usize>,
+        page: usize,
+        pages: usize,
+    ) -> Result<String> {
+        let mut j = serde_json::to_value(self)?;
+        j["details"] = self.sections[range.clone()].join("\n").into();
+        j["resources"] = serde_json::to_value(&self.resources[range])?;
+        if page > 1 {
+            for key in [
+                "groups",
+                "configured_kinds",
+                "created_kinds",
+                "pruned_kinds",
+                "replaced_kinds",
+                "ignored_kinds",
+            ] {
+                j[key] = serde_json::Value::Array(Vec::new());
+            }
+        }
+        j["page"] = page.into();
+        j["pages"] = pages.into();
+        let title = reg.render_template(&self.title_template, &j)?;
+        let title = match title.split_once('\n') {
+            Some((first, rest)) => format!("{first} (part {page}/{pages})\n{rest}"),
+            None => format!("{title} (part {page}/{pages})"),
+        };
+        let body = reg.render_template(&self.body_template, &j)?;
+        Ok(format!("{title}{body}"))
+    }
+
+    /// Cut a page exceeding `limit` bytes, e.g. with the diff of a single huge resource.
+    fn fit(page: String, limit: usize) -> String {
+        if page.len() <= limit {
+            return page;
+        }
+        let footer = "\n```\n\n(truncated, see the CI job log for the full diff)\n";
+        let mut end = limit.saturating_sub(footer.len());
+        while !page.is_char_boundary(end) {
+            end -= 1;
+        }
+        let end = page[..end].rfind('\n').unwrap_or(end);
+        let mut cut = page[..end].to_string();
+        if cut.matches("```").count() % 2 == 1 {
+            cut.push_str(footer);
+        } else {
+            cut.push_str(&footer[4..]);
+        }
+        cut
+    }
+
+    /// Page number of a rendered page from the "(part i/n)" marker in its first line, 1 if none.
+    pub fn page_number(rendered_string: &str) -> usize {
+        rendered_string
+            .lines()
+            .next()
+            .and_then(|title| PAGE_MARKER.captures(title))
+            .and_then(|caps| caps[1].parse().ok())
+            .unwrap_or(1)
+    }
+
+    /// Whether there is no changed resource to report.
+    pub const fn is_no_changes(&self) -> bool {
+        self.is_no_changes
+    }
+
     pub fn is_same_build(&self, rendered_string: &str) -> Result<bool> {
         if self.target.is_none() {
             return Ok(false);
         }
+        self.is_same_title(rendered_string)
+    }
 
+    /// Whether the first line of `rendered_string` is the title of this template, ignoring the
+    /// page marker. Unlike `is_same_build`, it does not need a target, for the platforms marking
+    /// the comments of ksnotify by other means.
+    pub fn is_same_title(&self, rendered_string: &str) -> Result<bool> {
         let old_title = match rendered_string.lines().next() {
-            // take first line (it should be title)
-            Some(title) => title,
+            // take first line (it should be title) without the page marker
+            Some(title) => PAGE_MARKER.replace(title, ""),
             None => return Ok(false),
         };
 
-        let reg = Handlebars::new();
+        let reg = self.registry()?;
         let j = serde_json::to_value(self)?;
-        let current_title = reg.render_template(Self::DEFAULT_BUILD_TITLE_TEMPLATE, &j)?;
+        let current_title = reg.render_template(&self.title_template, &j)?;
 
-        if current_title == old_title {
-            return Ok(true);
-        }
-
-        Ok(false)
+        Ok(current_title.lines().next() == Some(old_title.as_ref()))
     }
 
-    fn filter_kinds(
-        results: &HashMap<String, String>,
-        predicate: impl Fn(&str) -> bool,
-    ) -> Vec<String> {
+    fn filter_kinds(results: &[ResourceChange], action: Action) -> Vec<String> {
         results
             .iter()
-            .filter(|(_, e)| predicate(e.as_str()))
-            .map(|(k, _)| k.clone())
+            .filter(|e| e.action == action)
+            .map(ResourceChange::id)
             .sorted()
             .collect()
     }
 
-    fn generate_configured_kinds_markdown(results: &HashMap<String, String>) -> Vec<String> {
-        Self::filter_kinds(results, |e| {
-            !e.contains("-kind: ") && !e.contains("+kind: ")
-        })
+    fn generate_configured_kinds_markdown(results: &[ResourceChange]) -> Vec<String> {
+        Self::filter_kinds(results, Action::Configured)
+    }
+
+    fn generate_created_kinds_markdown(results: &[ResourceChange]) -> Vec<String> {
+        Self::filter_kinds(results, Action::Created)
     }
 
-    fn generate_created_kinds_markdown(results: &HashMap<String, String>) -> Vec<String> {
-        Self::filter_kinds(results, |e| e.contains("+kind: "))
+    fn generate_pruned_kinds_markdown(results: &[ResourceChange]) -> Vec<String> {
+        Self::filter_kinds(results, Action::Pruned)
     }
 
-    fn generate_pruned_kinds_markdown(results: &HashMap<String, String>) -> Vec<String> {
-        Self::filter_kinds(results, |e| e.contains("-kind: "))
+    fn generate_replaced_kinds_markdown(results: &[ResourceChange]) -> Vec<String> {
+        Self::filter_kinds(results, Action::Replaced)
     }
 
-    fn generate_details_markdown(results: &HashMap<String, String>) -> String {
-        let kinds: Vec<String> = results.keys().map(|e| e.to_string()).sorted().collect();
-        let details: Vec<String> = kinds
+    fn generate_detail_sections(results: &[ResourceChange]) -> Vec<String> {
+        results
             .iter()
-            .map(|k| {
-                let title = format!("### {k}");
-                let body = format!("```diff\n{}\n```", results[k]);
+            .sorted_by_key(|e| e.id())
+            .map(|e| {
+                let title = format!("### {}", e.id());
+                let body = e.changes.as_deref().map_or_else(
+                    || format!("```diff\n{}\n```", e.diff()),
+                    Self::generate_change_table_markdown,
+                );
                 format!("{title}\n{body}")
             })
-            .collect();
-        details.join("\n")
+            .collect()
+    }
+
+    fn generate_change_table_markdown(changes: &[PathChange]) -> String {
+        if changes.is_empty() {
+            return "No semantic changes (only ordering or formatting).".to_string();
+        }
+        let cell = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map_or_else(String::new, |v| format!("`{}`", v.replace('|', "\\|")))
+        };
+        let rows = changes
+            .iter()
+            .map(|c| {
+                format!(
+                    "| `{}` | {} | {} |",
+                    c.path,
+                    cell(&c.before),
+                    cell(&c.after)
+                )
+            })
+            .join("\n");
+        format!("| path | before | after |\n| --- | --- | --- |\n{rows}")
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
 
     #[test]
     fn test_render_for_created_kinds() {
-        let data = HashMap::from([(
-            "apps.v1.Deployment.default.hoge".to_string(),
-            "+apiVersion: apps/v1
+        let data = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.hoge",
+                "+apiVersion: apps/v1
 +kind: Deployment
 +metadata:
-+  name: hoge"
-                .to_string(),
-        )]);
++  name: hoge",
+            )
+            .unwrap(),
+        ];
         let template = Template::new(
             data,
             "https://example.com".to_string(),
@@ -167,6 +746,11 @@ mod tests {
 ## created
 * apps.v1.Deployment.default.hoge
 
+| resource | action | added | removed |
+| --- | --- | --- | --- |
+| apps.v1.Deployment.default.hoge | created | +4 | -0 |
+| **total** | | +4 | -0 |
+
 <details><summary>Details (Click me)</summary>
 
 ### apps.v1.Deployment.default.hoge
@@ -185,14 +769,16 @@ mod tests {
 
     #[test]
     fn test_render_for_pruned_kinds() {
-        let data = HashMap::from([(
-            "apps.v1.Deployment.default.hoge".to_string(),
-            "-apiVersion: apps/v1
+        let data = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.hoge",
+                "-apiVersion: apps/v1
 -kind: Deployment
 -metadata:
--  name: hoge"
-                .to_string(),
-        )]);
+-  name: hoge",
+            )
+            .unwrap(),
+        ];
         let template = Template::new(
             data,
             "https://example.com".to_string(),
@@ -205,6 +791,11 @@ mod tests {
 ## pruned
 * apps.v1.Deployment.default.hoge
 
+| resource | action | added | removed |
+| --- | --- | --- | --- |
+| apps.v1.Deployment.default.hoge | pruned | +0 | -4 |
+| **total** | | +0 | -4 |
+
 <details><summary>Details (Click me)</summary>
 
 ### apps.v1.Deployment.default.hoge
@@ -223,12 +814,14 @@ mod tests {
 
     #[test]
     fn test_render_for_configured_kinds() {
-        let data = HashMap::from([(
-            "apps.v1.Deployment.default.hoge".to_string(),
-            "-  image: hoge
-+  image: fuga"
-                .to_string(),
-        )]);
+        let data = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.hoge",
+                "-  image: hoge
++  image: fuga",
+            )
+            .unwrap(),
+        ];
         let template = Template::new(
             data,
             "https://example.com".to_string(),
@@ -241,6 +834,11 @@ mod tests {
 ## configured
 * apps.v1.Deployment.default.hoge
 
+| resource | action | added | removed |
+| --- | --- | --- | --- |
+| apps.v1.Deployment.default.hoge | configured | +1 | -1 |
+| **total** | | +1 | -1 |
+
 <details><summary>Details (Click me)</summary>
 
 ### apps.v1.Deployment.default.hoge
@@ -280,7 +878,7 @@ mod tests {
 
     #[test]
     fn test_render_body_with_no_changes() {
-        let data: HashMap<String, String> = HashMap::new();
+        let data = Vec::new();
         let template = Template::new(
             data,
             "https://example.com".to_string(),
@@ -304,10 +902,21 @@ No changes. Kubernetes configurations are up-to-date.
             target: None,
             created_kinds: Vec::new(),
             pruned_kinds: Vec::new(),
+            replaced_kinds: Vec::new(),
+            ignored_kinds: Vec::new(),
+            groups: Vec::new(),
+            resources: Vec::new(),
+            counts: Counts::default(),
+            stats: LineStats::default(),
             configured_kinds: Vec::new(),
             details: "test".to_string(),
             link: "http://example.com".to_string(),
             is_no_changes: false,
+            title_template: Template::DEFAULT_BUILD_TITLE_TEMPLATE.to_string(),
+            body_template: Template::DEFAULT_BUILD_BODY_TEMPLATE.to_string(),
+            partials: BTreeMap::new(),
+            sections: Vec::new(),
+            max_comment_size: None,
         };
         assert!(!template.is_same_build("test").unwrap());
     }
@@ -318,10 +927,21 @@ No changes. Kubernetes configurations are up-to-date.
             target: Some("test".to_string()),
             created_kinds: Vec::new(),
             pruned_kinds: Vec::new(),
+            replaced_kinds: Vec::new(),
+            ignored_kinds: Vec::new(),
+            groups: Vec::new(),
+            resources: Vec::new(),
+            counts: Counts::default(),
+            stats: LineStats::default(),
             configured_kinds: Vec::new(),
             details: "test".to_string(),
             link: "http://example.com".to_string(),
             is_no_changes: false,
+            title_template: Template::DEFAULT_BUILD_TITLE_TEMPLATE.to_string(),
+            body_template: Template::DEFAULT_BUILD_BODY_TEMPLATE.to_string(),
+            partials: BTreeMap::new(),
+            sections: Vec::new(),
+            max_comment_size: None,
         };
         assert!(template.is_same_build("## Plan result (test)").unwrap())
     }
@@ -332,10 +952,21 @@ No changes. Kubernetes configurations are up-to-date.
             target: Some("test1".to_string()),
             created_kinds: Vec::new(),
             pruned_kinds: Vec::new(),
+            replaced_kinds: Vec::new(),
+            ignored_kinds: Vec::new(),
+            groups: Vec::new(),
+            resources: Vec::new(),
+            counts: Counts::default(),
+            stats: LineStats::default(),
             configured_kinds: Vec::new(),
             details: "test".to_string(),
             link: "http://example.com".to_string(),
             is_no_changes: false,
+            title_template: Template::DEFAULT_BUILD_TITLE_TEMPLATE.to_string(),
+            body_template: Template::DEFAULT_BUILD_BODY_TEMPLATE.to_string(),
+            partials: BTreeMap::new(),
+            sections: Vec::new(),
+            max_comment_size: None,
         };
         assert!(!template.is_same_build("## Plan result (test2)").unwrap())
     }
@@ -346,17 +977,28 @@ No changes. Kubernetes configurations are up-to-date.
             target: Some("test".to_string()),
             created_kinds: Vec::new(),
             pruned_kinds: Vec::new(),
+            replaced_kinds: Vec::new(),
+            ignored_kinds: Vec::new(),
+            groups: Vec::new(),
+            resources: Vec::new(),
+            counts: Counts::default(),
+            stats: LineStats::default(),
             configured_kinds: Vec::new(),
             details: "test".to_string(),
             link: "http://example.com".to_string(),
             is_no_changes: false,
+            title_template: Template::DEFAULT_BUILD_TITLE_TEMPLATE.to_string(),
+            body_template: Template::DEFAULT_BUILD_BODY_TEMPLATE.to_string(),
+            partials: BTreeMap::new(),
+            sections: Vec::new(),
+            max_comment_size: None,
         };
         assert!(!template.is_same_build("").unwrap());
     }
 
     #[test]
     fn test_render_without_target() {
-        let data: HashMap<String, String> = HashMap::new();
+        let data = Vec::new();
         let template = Template::new(data, "https://example.com".to_string(), None);
         let actual = template.render().unwrap();
         assert!(actual.starts_with("## Plan result\n"));
@@ -364,16 +1006,10 @@ No changes. Kubernetes configurations are up-to-date.
 
     #[test]
     fn test_generate_configured_kinds_markdown() {
-        let results = HashMap::from([
-            (
-                "v1.Service.default.svc-b".to_string(),
-                "-  port: 80\n+  port: 8080".to_string(),
-            ),
-            (
-                "v1.Service.default.svc-a".to_string(),
-                "-  port: 80\n+  port: 8080".to_string(),
-            ),
-        ]);
+        let results = vec![
+            ResourceChange::new("v1.Service.default.svc-b", "-  port: 80\n+  port: 8080").unwrap(),
+            ResourceChange::new("v1.Service.default.svc-a", "-  port: 80\n+  port: 8080").unwrap(),
+        ];
         let actual = Template::generate_configured_kinds_markdown(&results);
         assert_eq!(
             actual,
@@ -386,20 +1022,19 @@ No changes. Kubernetes configurations are up-to-date.
 
     #[test]
     fn test_generate_created_kinds_markdown() {
-        let results = HashMap::from([
-            (
-                "apps.v1.Deployment.default.dep-b".to_string(),
-                "+kind: Deployment\n+  name: dep-b".to_string(),
-            ),
-            (
-                "apps.v1.Deployment.default.dep-a".to_string(),
-                "+kind: Deployment\n+  name: dep-a".to_string(),
-            ),
-            (
-                "v1.Service.default.svc".to_string(),
-                "-  port: 80\n+  port: 8080".to_string(),
-            ),
-        ]);
+        let results = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.dep-b",
+                "+kind: Deployment\n+  name: dep-b",
+            )
+            .unwrap(),
+            ResourceChange::new(
+                "apps.v1.Deployment.default.dep-a",
+                "+kind: Deployment\n+  name: dep-a",
+            )
+            .unwrap(),
+            ResourceChange::new("v1.Service.default.svc", "-  port: 80\n+  port: 8080").unwrap(),
+        ];
         let actual = Template::generate_created_kinds_markdown(&results);
         assert_eq!(
             actual,
@@ -412,20 +1047,19 @@ No changes. Kubernetes configurations are up-to-date.
 
     #[test]
     fn test_generate_pruned_kinds_markdown() {
-        let results = HashMap::from([
-            (
-                "apps.v1.Deployment.default.dep-b".to_string(),
-                "-kind: Deployment\n-  name: dep-b".to_string(),
-            ),
-            (
-                "apps.v1.Deployment.default.dep-a".to_string(),
-                "-kind: Deployment\n-  name: dep-a".to_string(),
-            ),
-            (
-                "v1.Service.default.svc".to_string(),
-                "-  port: 80\n+  port: 8080".to_string(),
-            ),
-        ]);
+        let results = vec![
+            ResourceChange::new(
+                "apps.v1.Deployment.default.dep-b",
+                "-kind: Deployment\n-  name: dep-b",
+            )
+            .unwrap(),
+            ResourceChange::new(
+                "apps.v1.Deployment.default.dep-a",
+                "-kind: Deployment\n-  name: dep-a",
+            )
+            .unwrap(),
+            ResourceChange::new("v1.Service.default.svc", "-  port: 80\n+  port: 8080").unwrap(),
+        ];
         let actual = Template::generate_pruned_kinds_markdown(&results);
         assert_eq!(
             actual,
@@ -436,14 +1070,440 @@ No changes. Kubernetes configurations are up-to-date.
         );
     }
 
+    #[test]
+    fn test_generate_replaced_kinds_markdown() {
+        let results = vec![
+            ResourceChange::new(
+                "v1.Service.default.svc",
+                "@@ -1,2 +1,2 @@\n-kind: Service\n-  port: 80\n+kind: Service\n+  port: 8080",
+            )
+            .unwrap(),
+            ResourceChange::new(
+                "v1.ConfigMap.default.cm",
+                "@@ -3 +3 @@\n-  key: a\n+  key: b",
+            )
+            .unwrap(),
+        ];
+        let actual = Template::generate_replaced_kinds_markdown(&results);
+        assert_eq!(actual, vec!["v1.Service.default.svc".to_string()]);
+    }
+
     #[test]
     fn test_generate_details_markdown_sorted_by_kind() {
-        let results = HashMap::from([
-            ("kind-b".to_string(), "diff-b".to_string()),
-            ("kind-a".to_string(), "diff-a".to_string()),
+        let results = vec![
+            ResourceChange::new("v1.Service.default.svc-b", "diff-b").unwrap(),
+            ResourceChange::new("v1.Service.default.svc-a", "diff-a").unwrap(),
+        ];
+        let actual = Template::generate_detail_sections(&results).join("\n");
+        let expected = "### v1.Service.default.svc-a\n```diff\ndiff-a\n```\n### v1.Service.default.svc-b\n```diff\ndiff-b\n```";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_generate_details_markdown_renders_semantic_changes_as_table() {
+        let mut change = ResourceChange::new("apps.v1.Deployment.default.app", "diff").unwrap();
+        change.changes = Some(vec![
+            PathChange {
+                path: "spec.template.spec.containers[name=app].image".to_string(),
+                before: Some("app:1".to_string()),
+                after: Some("app:2".to_string()),
+            },
+            PathChange {
+                path: "metadata.labels.tier".to_string(),
+                before: None,
+                after: Some("a|b".to_string()),
+            },
         ]);
-        let actual = Template::generate_details_markdown(&results);
-        let expected = "### kind-a\n```diff\ndiff-a\n```\n### kind-b\n```diff\ndiff-b\n```";
+        let actual = Template::generate_detail_sections(&[change]).join("\n");
+        let expected = "### apps.v1.Deployment.default.app
+| path | before | after |
+| --- | --- | --- |
+| `spec.template.spec.containers[name=app].image` | `app:1` | `app:2` |
+| `metadata.labels.tier` |  | `a\\|b` |";
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn test_generate_details_markdown_without_semantic_changes() {
+        let mut change = ResourceChange::new("v1.Service.default.svc", "diff").unwrap();
+        change.changes = Some(Vec::new());
+        let actual = Template::generate_detail_sections(&[change]).join("\n");
+        assert_eq!(
+            actual,
+            "### v1.Service.default.svc\nNo semantic changes (only ordering or formatting)."
+        );
+    }
+
+    #[test]
+    fn test_render_with_ignored_resources() {
+        let data = vec![ResourceChange::new("v1.Service.default.web", " a\n-b\n+c").unwrap()];
+        let ignored = vec![
+            ResourceChange::new("v1.Secret.default.token", "-a\n+b").unwrap(),
+            ResourceChange::new("v1.ConfigMap.default.conf", "+a").unwrap(),
+        ];
+        let template =
+            Template::new(data, "https://example.com".to_string(), None).with_ignored(&ignored);
+        let actual = template.render().unwrap();
+        assert!(actual.ends_with(
+            "</details>
+
+<details><summary>ignored (2)</summary>
+
+* v1.ConfigMap.default.conf (created)
+* v1.Secret.default.token (configured)
+
+</details>
+"
+        ));
+    }
+
+    #[test]
+    fn test_render_with_only_ignored_resources() {
+        let ignored = vec![ResourceChange::new("v1.Secret.default.token", "-a\n+b").unwrap()];
+        let template = Template::new(Vec::new(), "https://example.com".to_string(), None)
+            .with_ignored(&ignored);
+        let actual = template.render().unwrap();
+        assert!(actual.contains("No changes. Kubernetes configurations are up-to-date."));
+        assert!(actual.contains("* v1.Secret.default.token (configured)"));
+    }
+
+    #[test]
+    fn test_render_with_custom_templates() {
+        let data = vec![ResourceChange::new("v1.Service.default.web", " a\n-b\n+c").unwrap()];
+        let template = Template::new(data, "https://example.com".to_string(), None)
+            .with_templates(
+                Some("# Diff of {{#if target}}{{target}}{{else}}all{{/if}}\n".to_string()),
+                Some(
+                    "{{#each configured_kinds}}- [ ] {{this}}\n{{/each}}See [runbook](https://example.com/runbook) and [CI]({{link}})".to_string(),
+                ),
+            );
+        let actual = template.render().unwrap();
+        let expected = "# Diff of all
+- [ ] v1.Service.default.web
+See [runbook](https://example.com/runbook) and [CI](https://example.com)";
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_is_same_build_with_custom_title_template() {
+        let template = Template::new(Vec::new(), String::new(), Some("prod".to_string()))
+            .with_templates(
+                Some("### ksnotify: {{target}}\nsecond line".to_string()),
+                None,
+            );
+        assert!(
+            template
+                .is_same_build("### ksnotify: prod\nother body")
+                .unwrap()
+        );
+        assert!(
+            !template
+                .is_same_build("## Plan result (prod)\nother body")
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_template_source_deserializes_inline_and_file() {
+        let inline: TemplateSource = serde_yml::from_str("\"## {{target}}\"").unwrap();
+        assert_eq!(inline, TemplateSource::Inline("## {{target}}".to_string()));
+        let file: TemplateSource = serde_yml::from_str("file: body.hbs").unwrap();
+        assert_eq!(
+            file,
+            TemplateSource::File {
+                file: PathBuf::from("body.hbs")
+            }
+        );
+    }
+
+    #[test]
+    fn test_template_source_loads_file_relative_to_base_dir() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("body.hbs"), "{{link}}").unwrap();
+        let source = TemplateSource::File {
+            file: PathBuf::from("body.hbs"),
+        }
+        .relative_to(temp_dir.path());
+        assert_eq!(source.load().unwrap(), "{{link}}");
+
+        let missing = TemplateSource::File {
+            file: temp_dir.path().join("missing.hbs"),
+        };
+        assert!(missing.load().is_err());
+    }
+
+    #[test]
+    fn test_render_with_partials_in_title_and_body() {
+        let data = vec![
+            ResourceChange::new("v1.Service.default.web", " a\n-b\n+c").unwrap(),
+            ResourceChange::new("apps.v1.Deployment.default.app", "+a").unwrap(),
+        ];
+        let partials = BTreeMap::from([
+            (
+                "env".to_string(),
+                "{{#if target}}{{target}}{{else}}default{{/if}}".to_string(),
+            ),
+            (
+                "row".to_string(),
+                "| {{short_kind kind}}/{{name}} | +{{added}} |".to_string(),
+            ),
+        ]);
+        let template = Template::new(data, String::new(), Some("prod".to_string()))
+            .with_templates(
+                Some("## Diff ({{> env}})\n".to_string()),
+                Some("{{#each resources}}{{> row}}\n{{/each}}{{> env}}".to_string()),
+            )
+            .with_partials(partials);
+        let actual = template.render().unwrap();
+        let expected = "## Diff (prod)
+| deploy/app | +1 |
+| svc/web | +1 |
+prod";
+        assert_eq!(actual, expected);
+        assert!(template.is_same_build("## Diff (prod)\nold body").unwrap());
+    }
+
+    #[test]
+    fn test_render_payload() {
+        let data = vec![ResourceChange::new("v1.Service.default.web", "-a\n+b").unwrap()];
+        let template = Template::new(data, "https://example.com/job".to_string(), None);
+        let actual = template
+            .render_payload(
+                r#"{"title": {{json title}}, "link": "{{link}}", "ids": [{{#each resources}}{{#unless @first}}, {{/unless}}{{json id}}{{/each}}], "text": {{json comment}}}"#,
+            )
+            .unwrap();
+        assert_eq!(
+            actual,
+            serde_json::json!({
+                "title": "## Plan result",
+                "link": "https://example.com/job",
+                "ids": ["v1.Service.default.web"],
+                "text": template.render().unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_payload_not_json_returns_error() {
+        let template = Template::new(Vec::new(), String::new(), None);
+        assert!(template.render_payload("{\"text\": {{title}}}").is_err());
+    }
+
+    #[test]
+    fn test_render_with_unknown_partial_returns_error() {
+        let template = Template::new(Vec::new(), String::new(), None)
+            .with_templates(None, Some("{{> missing}}".to_string()));
+        assert!(template.render().is_err());
+    }
+
+    fn large_resources() -> Vec<ResourceChange> {
+        ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                let diff = format!("-  image: {name}:1\n+  image: {name}:2\n").repeat(20);
+                ResourceChange::new(&format!("v1.Pod.default.{name}"), &diff).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_render_pages_without_limit_returns_whole_comment() {
+        let template = Template::new(large_resources(), String::new(), Some("t".to_string()));
+        let actual = template.render_pages(None).unwrap();
+        assert_eq!(actual, vec![template.render().unwrap()]);
+    }
+
+    #[test]
+    fn test_render_pages_splits_at_resource_boundaries() {
+        let template = Template::new(large_resources(), String::new(), Some("t".to_string()))
+            .with_max_comment_size(Some(1000));
+        let actual = template.render_pages(Some(65536)).unwrap();
+        assert_eq!(actual.len(), 3);
+        for (i, page) in actual.iter().enumerate() {
+            assert!(page.len() <= 1000);
+            assert!(page.starts_with(&format!("## Plan result (t) (part {}/3)\n", i + 1)));
+            assert_eq!(page.matches("### v1.Pod.default.").count(), 1);
+            assert_eq!(Template::page_number(page), i + 1);
+            assert!(template.is_same_build(page).unwrap());
+        }
+        // the lists of resources are only on the first page
+        assert!(actual[0].contains("## configured\n* v1.Pod.default.a\n"));
+        assert!(!actual[1].contains("## configured"));
+        assert!(actual[1].contains("### v1.Pod.default.b"));
+    }
+
+    #[test]
+    fn test_render_pages_keeps_every_resource_of_many_small_ones() {
+        let data = (0..200)
+            .map(|i| ResourceChange::new(&format!("v1.Pod.default.pod-{i:03}"), "-a\n+b").unwrap())
+            .collect();
+        let template = Template::new(data, String::new(), Some("t".to_string()));
+        let actual = template.render_pages(Some(10_000)).unwrap();
+        assert!(actual.len() > 1);
+        for page in &actual {
+            assert!(page.len() <= 10_000);
+            assert!(!page.contains("(truncated, see the CI job log for the full diff)"));
+        }
+        for i in 0..200 {
+            let id = format!("### v1.Pod.default.pod-{i:03}\n");
+            assert!(actual.iter().any(|page| page.contains(&id)), "{id} is lost");
+        }
+    }
+
+    #[test]
+    fn test_render_pages_truncates_oversized_resource() {
+        let data = vec![ResourceChange::new("v1.Pod.default.a", &"+x\n".repeat(1000)).unwrap()];
+        let template = Template::new(data, String::new(), Some("t".to_string()));
+        let actual = template.render_pages(Some(500)).unwrap();
+        assert_eq!(actual.len(), 1);
+        assert!(actual[0].len() <= 500);
+        assert!(actual[0].ends_with("```\n\n(truncated, see the CI job log for the full diff)\n"));
+    }
+
+    #[test]
+    fn test_page_number_without_marker() {
+        assert_eq!(Template::page_number("## Plan result (t)\nbody"), 1);
+        assert_eq!(Template::page_number(""), 1);
+    }
+
+    #[test]
+    fn test_with_truncation_cuts_long_diffs_with_summary() {
+        let data = vec![
+            ResourceChange::new("v1.Pod.default.a", " a\n-b\n+c\n+d\n e").unwrap(),
+            ResourceChange::new("v1.Pod.default.b", "-x").unwrap(),
+        ];
+        let template = Template::new(data, "https://example.com/job".to_string(), None)
+            .with_truncation(Some(2), None);
+        let expected = "### v1.Pod.default.a
+```diff
+ a
+-b
+```
+… 3 more lines (+2/-0) truncated, see [job log](https://example.com/job)
+### v1.Pod.default.b
+```diff
+-x
+```";
+        assert_eq!(template.details, expected);
+    }
+
+    #[test]
+    fn test_with_truncation_cuts_biggest_diffs_first() {
+        let data = vec![
+            ResourceChange::new("v1.Pod.default.big", &"+0123456789\n".repeat(100)).unwrap(),
+            ResourceChange::new("v1.Pod.default.medium", &"+0123456789\n".repeat(20)).unwrap(),
+            ResourceChange::new("v1.Pod.default.small", "+x").unwrap(),
+        ];
+        let template = Template::new(data, String::new(), None).with_truncation(None, Some(600));
+        assert!(template.details.len() <= 600);
+        assert!(template.sections[0].contains("more lines (+"));
+        assert!(template.sections[0].ends_with("truncated, see job log"));
+        assert!(!template.sections[1].contains("truncated"));
+        assert!(!template.sections[2].contains("truncated"));
+    }
+
+    #[test]
+    fn test_with_truncation_keeps_diffs_within_budget() {
+        let data = large_resources();
+        let template = Template::new(data, String::new(), None);
+        let details = template.details.clone();
+        let template = template.with_truncation(Some(1000), Some(100_000));
+        assert_eq!(template.details, details);
+    }
+
+    #[test]
+    fn test_size_cap() {
+        assert_eq!(Template::size_cap(&[10, 20], 100), usize::MAX);
+        assert_eq!(Template::size_cap(&[10, 500, 300], 310), 150);
+    }
+
+    #[test]
+    fn test_render_summary_table_with_total_stats() {
+        let data = vec![
+            ResourceChange::new("v1.Service.default.a", " a\n-b\n+c\n+d").unwrap(),
+            ResourceChange::new("v1.Service.default.b", "-x").unwrap(),
+        ];
+        let template = Template::new(data, String::new(), None);
+        assert_eq!(
+            template.stats,
+            LineStats {
+                added: 2,
+                removed: 2
+            }
+        );
+        let actual = template.render().unwrap();
+        assert!(actual.contains(
+            "| v1.Service.default.a | configured | +2 | -1 |
+| v1.Service.default.b | pruned | +0 | -1 |
+| **total** | | +2 | -2 |"
+        ));
+    }
+
+    #[test]
+    fn test_render_counts_in_custom_template() {
+        let data = vec![
+            ResourceChange::new("v1.Service.default.a", " a\n-b\n+c").unwrap(),
+            ResourceChange::new("v1.Service.default.b", "-x").unwrap(),
+        ];
+        let ignored = vec![ResourceChange::new("v1.Secret.default.s", "+a").unwrap()];
+        let template = Template::new(data, String::new(), None)
+            .with_ignored(&ignored)
+            .with_templates(
+                None,
+                Some(
+                    "{{counts.total}}: {{counts.configured}} configured, {{counts.pruned}} pruned, {{counts.ignored}} ignored"
+                        .to_string(),
+                ),
+            );
+        let actual = template.render().unwrap();
+        assert!(actual.ends_with("2: 1 configured, 1 pruned, 1 ignored"));
+    }
+
+    #[test]
+    fn test_render_with_grouped_layout() {
+        let data = vec![
+            ResourceChange::new("v1.Service.prod.web", "-a\n+b").unwrap(),
+            ResourceChange::new("apps.v1.Deployment.prod.web", "-a\n+b").unwrap(),
+            ResourceChange::new("apps.v1.Deployment.prod.api", "+a").unwrap(),
+            ResourceChange::new("v1.Namespace..prod", "+a").unwrap(),
+        ];
+        let template = Template::new(data, String::new(), None).with_grouped_layout(true);
+        let actual = template.render().unwrap();
+        let expected = "<details><summary>(cluster) (1)</summary>
+
+<details><summary>Namespace (1)</summary>
+
+* prod (created)
+
+</details>
+
+</details>
+<details><summary>prod (3)</summary>
+
+<details><summary>Deployment (2)</summary>
+
+* api (created)
+* web (configured)
+
+</details>
+<details><summary>Service (1)</summary>
+
+* web (configured)
+
+</details>
+
+</details>
+";
+        assert!(actual.contains(expected));
+        assert!(!actual.contains("## configured"));
+    }
+
+    #[test]
+    fn test_render_without_grouped_layout_keeps_flat_lists() {
+        let data = vec![ResourceChange::new("v1.Service.prod.web", "-a\n+b").unwrap()];
+        let template = Template::new(data, String::new(), None).with_grouped_layout(false);
+        assert!(template.groups.is_empty());
+        let actual = template.render().unwrap();
+        assert!(actual.contains("## configured\n* v1.Service.prod.web\n"));
+    }
 }
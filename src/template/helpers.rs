@@ -0,0 +1,192 @@
+use handlebars::{Handlebars, handlebars_helper};
+use itertools::Itertools;
+use serde_json::{Value, json};
+
+handlebars_helper!(group_by_namespace: |resources: array| group_by_namespace_of(resources));
+handlebars_helper!(count_lines_added: |resources: Json| count_lines(resources, "added"));
+handlebars_helper!(count_lines_removed: |resources: Json| count_lines(resources, "removed"));
+handlebars_helper!(filter_kind: |resources: array, kind: str| {
+    resources
+        .iter()
+        .filter(|r| r.get("kind").and_then(Value::as_str) == Some(kind))
+        .cloned()
+        .collect::<Vec<_>>()
+});
+handlebars_helper!(truncate: |text: str, length: u64| truncate_chars(text, length));
+handlebars_helper!(short_kind: |kind: str| short_kind_of(kind));
+handlebars_helper!(code_fence: |text: str, {lang: str = ""}| code_fence_of(text, lang));
+handlebars_helper!(json: |value: Json| value.to_string());
+
+/// Register the ksnotify helpers to `reg`.
+pub fn register(reg: &mut Handlebars) {
+    reg.register_helper("group_by_namespace", Box::new(group_by_namespace));
+    reg.register_helper("count_lines_added", Box::new(count_lines_added));
+    reg.register_helper("count_lines_removed", Box::new(count_lines_removed));
+    reg.register_helper("filter_kind", Box::new(filter_kind));
+    reg.register_helper("truncate", Box::new(truncate));
+    reg.register_helper("short_kind", Box::new(short_kind));
+    reg.register_helper("code_fence", Box::new(code_fence));
+    reg.register_helper("json", Box::new(json));
+}
+
+/// Group resources into `[{namespace, resources}]` sorted by namespace.
+fn group_by_namespace_of(resources: &[Value]) -> Value {
+    let groups = resources
+        .iter()
+        .into_group_map_by(|r| {
+            r.get("namespace")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        })
+        .into_iter()
+        .sorted_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(namespace, resources)| json!({"namespace": namespace, "resources": resources}))
+        .collect();
+    Value::Array(groups)
+}
+
+/// Sum of `field` of a resource or a list of resources.
+fn count_lines(value: &Value, field: &str) -> u64 {
+    match value {
+        Value::Array(resources) => resources.iter().map(|r| count_lines(r, field)).sum(),
+        _ => value.get(field).and_then(Value::as_u64).unwrap_or_default(),
+    }
+}
+
+/// Cut `text` to `length` characters followed by an ellipsis if it is longer.
+pub fn truncate_chars(text: &str, length: u64) -> String {
+    let length = usize::try_from(length).unwrap_or(usize::MAX);
+    if text.chars().count() <= length {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(length).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Short name of a kind as in `kubectl api-resources`, or the lowercase kind if it has none.
+fn short_kind_of(kind: &str) -> String {
+    let short = match kind {
+        "ConfigMap" => "cm",
+        "CronJob" => "cj",
+        "CustomResourceDefinition" => "crd",
+        "DaemonSet" => "ds",
+        "Deployment" => "deploy",
+        "Endpoints" => "ep",
+        "HorizontalPodAutoscaler" => "hpa",
+        "Ingress" => "ing",
+        "LimitRange" => "limits",
+        "Namespace" => "ns",
+        "NetworkPolicy" => "netpol",
+        "PersistentVolume" => "pv",
+        "PersistentVolumeClaim" => "pvc",
+        "Pod" => "po",
+        "PodDisruptionBudget" => "pdb",
+        "PriorityClass" => "pc",
+        "ReplicaSet" => "rs",
+        "ReplicationController" => "rc",
+        "ResourceQuota" => "quota",
+        "Service" => "svc",
+        "ServiceAccount" => "sa",
+        "StatefulSet" => "sts",
+        "StorageClass" => "sc",
+        _ => return kind.to_lowercase(),
+    };
+    short.to_string()
+}
+
+/// Wrap `text` in a Markdown code block with a fence longer than any backtick run in it.
+fn code_fence_of(text: &str, lang: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    format!("{fence}{lang}\n{text}\n{fence}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, data: &Value) -> String {
+        let mut reg = Handlebars::new();
+        register(&mut reg);
+        reg.render_template(template, data).unwrap()
+    }
+
+    fn resources() -> Value {
+        json!({"resources": [
+            {"id": "v1.Service.web.app", "kind": "Service", "namespace": "web", "added": 2, "removed": 1},
+            {"id": "v1.Namespace..web", "kind": "Namespace", "namespace": "", "added": 5, "removed": 0},
+            {"id": "apps.v1.Deployment.web.app", "kind": "Deployment", "namespace": "web", "added": 1, "removed": 3},
+        ]})
+    }
+
+    #[test]
+    fn test_render_group_by_namespace() {
+        let actual = render(
+            "{{#each (group_by_namespace resources)}}[{{namespace}}]{{#each resources}} {{id}}{{/each}}\n{{/each}}",
+            &resources(),
+        );
+        assert_eq!(
+            actual,
+            "[] v1.Namespace..web\n[web] v1.Service.web.app apps.v1.Deployment.web.app\n"
+        );
+    }
+
+    #[test]
+    fn test_render_count_lines() {
+        let actual = render(
+            "+{{count_lines_added resources}}/-{{count_lines_removed resources}} {{#each resources}}+{{count_lines_added this}} {{/each}}",
+            &resources(),
+        );
+        assert_eq!(actual, "+8/-4 +2 +5 +1 ");
+    }
+
+    #[test]
+    fn test_render_filter_kind() {
+        let actual = render(
+            "{{#each (filter_kind resources \"Service\")}}{{id}}{{/each}} {{len (filter_kind resources \"Secret\")}}",
+            &resources(),
+        );
+        assert_eq!(actual, "v1.Service.web.app 0");
+    }
+
+    #[test]
+    fn test_render_truncate() {
+        let data = json!({"text": "abcdef", "multibyte": "あいうえお"});
+        let actual = render(
+            "{{truncate text 3}} {{truncate text 6}} {{truncate multibyte 2}}",
+            &data,
+        );
+        assert_eq!(actual, "abc… abcdef あい…");
+    }
+
+    #[test]
+    fn test_render_short_kind() {
+        let actual = render(
+            "{{#each resources}}{{short_kind kind}} {{/each}}{{short_kind \"Certificate\"}}",
+            &resources(),
+        );
+        assert_eq!(actual, "svc ns deploy certificate");
+    }
+
+    #[test]
+    fn test_render_code_fence() {
+        let data = json!({"diff": "-a\n+b", "markdown": "```yaml\nx: 1\n```"});
+        let actual = render("{{{code_fence diff lang=\"diff\"}}}", &data);
+        assert_eq!(actual, "```diff\n-a\n+b\n```");
+        let actual = render("{{{code_fence markdown}}}", &data);
+        assert_eq!(actual, "````\n```yaml\nx: 1\n```\n````");
+    }
+
+    #[test]
+    fn test_render_json() {
+        let data = json!({"text": "say \"hi\"\n", "stats": {"added": 1}});
+        let actual = render("{{{json text}}} {{{json stats}}} {{{json 3}}}", &data);
+        assert_eq!(actual, r#""say \"hi\"\n" {"added":1} 3"#);
+    }
+}
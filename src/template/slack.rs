@@ -0,0 +1,229 @@
+use super::helpers::truncate_chars;
+use super::{ResourceContext, Template};
+use crate::resource::Action;
+
+use serde_json::{Value, json};
+
+/// Lines of the diff shown per resource. Slack is for a summary, the full diff is in the CI job log.
+const MAX_DIFF_LINES: usize = 20;
+/// Resources with a diff attachment. The others are only counted.
+const MAX_ATTACHMENTS: usize = 20;
+/// Limit of the text of a section block in characters.
+const MAX_SECTION_TEXT: usize = 3000;
+/// Limit of the text of a header block in characters.
+const MAX_HEADER_TEXT: usize = 150;
+
+/// Block Kit payload of an incoming webhook with the title, the counts per action, the CI link
+/// and the diffs of the resources as attachments colored by action.
+pub fn render(template: &Template, title: &str) -> Value {
+    let title = truncate(title.trim_start_matches('#').trim(), MAX_HEADER_TEXT);
+    let mut blocks = vec![json!({
+        "type": "header",
+        "text": {"type": "plain_text", "text": title},
+    })];
+
+    if template.is_no_changes {
+        blocks.push(section(
+            "No changes. Kubernetes configurations are up-to-date.",
+        ));
+    } else {
+        let fields: Vec<Value> = [
+            Action::Created,
+            Action::Pruned,
+            Action::Replaced,
+            Action::Configured,
+        ]
+        .into_iter()
+        .map(|action| json!({"type": "mrkdwn", "text": format!("*{action}*\n{}", template.counts.of(action))}))
+        .chain([json!({
+            "type": "mrkdwn",
+            "text": format!("*lines*\n+{} / -{}", template.stats.added, template.stats.removed),
+        })])
+        .collect();
+        blocks.push(json!({"type": "section", "fields": fields}));
+    }
+    if template.counts.ignored > 0 {
+        blocks.push(json!({
+            "type": "context",
+            "elements": [{
+                "type": "mrkdwn",
+                "text": format!("{} resources ignored", template.counts.ignored),
+            }],
+        }));
+    }
+    if !template.link.is_empty() {
+        blocks.push(section(&format!("<{}|CI link>", template.link)));
+    }
+
+    let mut payload = json!({"text": title, "blocks": blocks});
+    if !template.is_no_changes {
+        let mut attachments: Vec<Value> = template
+            .resources
+            .iter()
+            .take(MAX_ATTACHMENTS)
+            .map(attachment)
+            .collect();
+        let rest = template.resources.len().saturating_sub(MAX_ATTACHMENTS);
+        if rest > 0 {
+            attachments.push(json!({
+                "blocks": [section(&format!("… and {rest} more resources, see the CI job log"))],
+            }));
+        }
+        payload["attachments"] = attachments.into();
+    }
+    payload
+}
+
+fn section(text: &str) -> Value {
+    json!({
+        "type": "section",
+        "text": {"type": "mrkdwn", "text": text},
+    })
+}
+
+/// Attachment with the head of the diff of a resource and a color bar by action.
+fn attachment(resource: &ResourceContext) -> Value {
+    let color = match resource.action {
+        Action::Created => "#2eb67d",
+        Action::Pruned => "#e01e5a",
+        Action::Replaced => "#ecb22e",
+        Action::Configured => "#36c5f0",
+    };
+    let heading = format!(
+        "*{}* ({}, +{}/-{})",
+        escape(&resource.id),
+        resource.action,
+        resource.added,
+        resource.removed
+    );
+    let lines: Vec<&str> = resource.diff.lines().collect();
+    let mut diff = escape(&lines[..lines.len().min(MAX_DIFF_LINES)].join("\n"));
+    let footer = if lines.len() > MAX_DIFF_LINES {
+        format!("\n… {} more lines", lines.len() - MAX_DIFF_LINES)
+    } else {
+        String::new()
+    };
+    // heading, fences and footer
+    let room = MAX_SECTION_TEXT.saturating_sub(heading.chars().count() + footer.len() + 16);
+    if diff.chars().count() > room {
+        diff = truncate(&diff, room);
+    }
+    json!({
+        "color": color,
+        "blocks": [section(&format!("{heading}\n```\n{diff}\n```{footer}"))],
+    })
+}
+
+/// Escape the control characters of Slack mrkdwn.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Cut `text` to at most `max` characters including the ellipsis.
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    truncate_chars(text, max.saturating_sub(1) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceChange;
+    use itertools::Itertools;
+
+    fn template(results: Vec<ResourceChange>) -> Template {
+        Template::new(
+            results,
+            "https://example.com/job".to_string(),
+            Some("prod".to_string()),
+        )
+    }
+
+    #[test]
+    fn test_render() {
+        let results = vec![
+            ResourceChange::new("v1.Service.default.svc", "+a\n+b").unwrap(),
+            ResourceChange::new("apps.v1.Deployment.default.app", " a\n-b\n+c").unwrap(),
+        ];
+        let actual = render(&template(results), "## Plan result (prod)");
+        assert_eq!(actual["text"], "Plan result (prod)");
+        assert_eq!(
+            actual["blocks"][0],
+            json!({"type": "header", "text": {"type": "plain_text", "text": "Plan result (prod)"}})
+        );
+        let fields: Vec<&str> = actual["blocks"][1]["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "*created*\n1",
+                "*pruned*\n0",
+                "*replaced*\n0",
+                "*configured*\n1",
+                "*lines*\n+3 / -1",
+            ]
+        );
+        assert_eq!(
+            actual["blocks"][2]["text"]["text"],
+            "<https://example.com/job|CI link>"
+        );
+        // sorted by id like the comment
+        assert_eq!(actual["attachments"][0]["color"], "#36c5f0");
+        assert_eq!(
+            actual["attachments"][0]["blocks"][0]["text"]["text"],
+            "*apps.v1.Deployment.default.app* (configured, +1/-1)\n```\n a\n-b\n+c\n```"
+        );
+        assert_eq!(actual["attachments"][1]["color"], "#2eb67d");
+    }
+
+    #[test]
+    fn test_render_no_changes() {
+        let actual = render(&template(Vec::new()), "## Plan result (prod)");
+        assert_eq!(
+            actual["blocks"][1]["text"]["text"],
+            "No changes. Kubernetes configurations are up-to-date."
+        );
+        assert!(actual.get("attachments").is_none());
+    }
+
+    #[test]
+    fn test_render_truncates_long_diff() {
+        let diff = (0..30).map(|i| format!("+line {i} <x>")).join("\n");
+        let results = vec![ResourceChange::new("v1.ConfigMap.default.cm", &diff).unwrap()];
+        let actual = render(&template(results), "title");
+        let text = actual["attachments"][0]["blocks"][0]["text"]["text"]
+            .as_str()
+            .unwrap();
+        assert!(text.contains("+line 19 &lt;x&gt;"));
+        assert!(!text.contains("+line 20"));
+        assert!(text.ends_with("```\n… 10 more lines"));
+    }
+
+    #[test]
+    fn test_render_limits_attachments() {
+        let results = (0..25)
+            .map(|i| ResourceChange::new(&format!("v1.Service.default.svc{i:02}"), "+a").unwrap())
+            .collect();
+        let actual = render(&template(results), "title");
+        let attachments = actual["attachments"].as_array().unwrap();
+        assert_eq!(attachments.len(), MAX_ATTACHMENTS + 1);
+        assert_eq!(
+            attachments[MAX_ATTACHMENTS]["blocks"][0]["text"]["text"],
+            "… and 5 more resources, see the CI job log"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abc", 3), "abc");
+        assert_eq!(truncate("abcdef", 4), "abc…");
+    }
+}